@@ -0,0 +1,40 @@
+// Frame time of the leg pipeline for growing swarms, run with `cargo bench --bench locomotion`.
+
+use std::time::{Duration, Instant};
+use bevy::prelude::*;
+use zombies::{ground::{Heightfield, HeightfieldGround}, headless::{run_ticks, spawn_rig, HeadlessPlugin}, leg::LegPlugin, lod::LodPlugin, IKArm::IKArmPlugin};
+
+const WARMUP_FRAMES: u32 = 10;
+const MEASURED_FRAMES: u32 = 100;
+
+fn main() {
+    for creature_count in [100, 1000, 5000] {
+        let frame_time = measure(creature_count);
+        println!(
+            "{creature_count:>5} creatures: {:>8.3} ms/frame",
+            frame_time.as_secs_f64() * 1000.
+        );
+    }
+}
+
+fn measure(creature_count: usize) -> Duration {
+    let mut app = App::new();
+    app.add_plugins(HeadlessPlugin)
+        .insert_resource(Heightfield::flat(0.))
+        .add_plugins((
+            IKArmPlugin::new().in_schedule(Update),
+            LegPlugin::<HeightfieldGround>::with_ground().in_schedule(Update),
+            LodPlugin::new().in_schedule(Update),
+        ));
+
+    let side = (creature_count as f32).sqrt().ceil() as usize;
+    for i in 0..creature_count {
+        let position = Vec3::new((i % side) as f32, 0.3, (i / side) as f32);
+        spawn_rig(app.world_mut(), position);
+    }
+
+    run_ticks(&mut app, WARMUP_FRAMES);
+    let start = Instant::now();
+    run_ticks(&mut app, MEASURED_FRAMES);
+    start.elapsed() / MEASURED_FRAMES
+}
@@ -0,0 +1,173 @@
+// The demo scene: a spider on the map and a procedural one next to it, WASD/QE to walk, Space to strike the blue cube, G to grab and throw it, J to jump.
+use std::f32::{consts::*, NAN};
+use bevy::{math::{NormedVectorSpace, VectorSpace}, prelude::*, render::mesh::{self, skinning::{SkinnedMesh, SkinnedMeshInverseBindposes}}};
+#[cfg(feature = "raycast")]
+use bevy_mod_raycast::prelude::NoBackfaceCulling;
+use rand::distributions::Standard;
+use zombies::{
+    attack::Attack,
+    grab::Grab,
+    jump::{Airborne, Jump},
+    leg::{IKLeg, KeyboardMovement, LegCreature, LegCreatureVisual, LegSide},
+    perception::Perceivable,
+    procedural::{LegAssets, LegShape},
+    spider::{spawn_procedural_spider, spawn_spider},
+    IKArm::IKArmTarget,
+    LocomotionPlugin,
+};
+
+#[derive(Component)]
+struct Movable;
+
+#[derive(Component)]
+struct MEsh;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(LocomotionPlugin::new())
+        .insert_resource(AmbientLight {
+            brightness: 750.0,
+            ..default()
+        })
+        .add_systems(Startup, (setup, ).chain())
+        .add_systems(Update, (movable, attack_movable, grab_movable, jump))
+       // .observe(modify_meshes)
+        .run();
+}
+
+#[cfg(feature = "raycast")]
+fn modify_meshes(
+    trigger: Trigger<OnAdd, Handle<Mesh>>,
+    mut commands: Commands,
+  ) {
+    commands
+      .entity(trigger.entity())
+      .insert(NoBackfaceCulling);
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>, mut inverse_bindposes: ResMut<Assets<SkinnedMeshInverseBindposes>>,) {
+    // Create a camera
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(-7.0, 7., -7.0)
+            .looking_at(Vec3::new(0.0, 0., 0.0), Vec3::Y),
+        ..default()
+    });
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Cuboid::new(0.1, 0.1, 0.1)),
+            material: materials.add(Color::srgb_u8(124, 144, 255)),
+            transform: Transform::from_xyz(-0.5, 0., 0.),
+            ..default()
+        },
+        Movable,
+        Perceivable::default(),
+    ));
+
+    let spider = spawn_spider(&mut commands, &asset_server, &mut meshes, &mut materials, Vec3::new(0., 0.3, 0.));
+    commands.entity(spider).insert(KeyboardMovement);
+
+    // A longer-legged variant built without leg.glb, it stands still next to the player's.
+    let long_legs = LegShape { lengths: vec![0.3, 0.45], taper: 0.25, ..default() };
+    let leg_material = materials.add(Color::srgb_u8(60, 40, 30));
+    let long_legs = LegAssets::new(long_legs, &mut meshes, &mut inverse_bindposes, leg_material);
+    spawn_procedural_spider(&mut commands, &long_legs, &mut meshes, &mut materials, Vec3::new(1.5, 0.3, 0.));
+        
+    commands.spawn(SceneBundle {
+        scene: asset_server.load("map/map.glb#Scene0"),
+        transform: Transform::from_xyz(0.0, 0.0, 0.0),
+        ..Default::default()
+    });
+    commands.spawn(PointLightBundle {
+        point_light: PointLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+
+}
+
+fn movable(
+    mut transform_query: Query<&mut Transform, With<Movable>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    for (mut movable_transform) in transform_query.iter_mut() {
+        let mut vec = Vec3::ZERO;
+        if keys.pressed(KeyCode::KeyW) {
+            vec.z += 1.0
+        }
+        if keys.pressed(KeyCode::KeyS) {
+            vec.z -= 1.0
+        }
+        if keys.pressed(KeyCode::KeyD) {
+            vec.x -= 1.0
+        }
+        if keys.pressed(KeyCode::KeyA) {
+            vec.x += 1.0
+        }
+        if keys.pressed(KeyCode::KeyQ) {
+            vec.y += 1.0
+        }
+        if keys.pressed(KeyCode::KeyE) {
+            vec.y -= 1.0
+        }
+        movable_transform.translation += vec * 0.01;
+    }
+}
+fn attack_movable(
+    mut commands: Commands,
+    creature_query: Query<&LegCreature>,
+    attack_query: Query<(), With<Attack>>,
+    target_query: Query<Entity, With<Movable>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+    let Some(target) = target_query.iter().next() else {return;};
+    for leg_creature in creature_query.iter() {
+        let Some(leg) = leg_creature.front_leg() else {continue;};
+        if attack_query.contains(leg) {
+            continue;
+        }
+        commands.entity(leg).insert((Attack::default(), IKArmTarget { target }));
+    }
+}
+
+fn grab_movable(
+    mut commands: Commands,
+    creature_query: Query<&LegCreature>,
+    mut grab_query: Query<&mut Grab>,
+    target_query: Query<Entity, With<Movable>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    let Some(target) = target_query.iter().next() else {return;};
+    for leg_creature in creature_query.iter() {
+        let Some(leg) = leg_creature.front_leg() else {continue;};
+        if let Ok(mut grab) = grab_query.get_mut(leg) {
+            grab.throw(Vec3::new(0., 2., 1.));
+            continue;
+        }
+        commands.entity(leg).insert((Grab::default(), IKArmTarget { target }));
+    }
+}
+
+fn jump(
+    mut commands: Commands,
+    creature_query: Query<Entity, (With<LegCreature>, Without<Airborne>, Without<Jump>)>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyJ) {
+        return;
+    }
+    for creature in creature_query.iter() {
+        commands.entity(creature).insert(Jump::new(Vec3::new(0., 3., 1.)));
+    }
+}
@@ -1,27 +1,144 @@
-use std::f32::consts::PI;
-use bevy::{prelude::*, render::mesh::{self, skinning::SkinnedMesh}};
+use bevy::{ecs::{entity::{EntityHashSet, MapEntities}, reflect::ReflectMapEntities, schedule::{InternedScheduleLabel, ScheduleLabel}}, prelude::*, render::mesh::{self, skinning::SkinnedMesh}};
 
-#[derive(Component)]
+use crate::{attack::Attack, debug::{DebugCategory, DebugGizmos}, grab::Grab, ik, inspect::ReadOnly, lod::{LocomotionLod, LodTier}, schedule::{configure_sets, LocomotionSet}};
+
+// How close solve_chain gets the tip to the target. How hard it tries depends on the LOD tier, see LodTier::ik_iterations.
+const CHAIN_TOLERANCE: f32 = 1e-4;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct IKArm {
     pub target: Vec3,
     pub up: Vec3,
+    #[reflect(@ReadOnly)]
+    end_effector: Vec3,
+}
+
+impl IKArm {
+    pub fn new(target: Vec3, up: Vec3) -> Self {
+        Self { target, up, end_effector: target }
+    }
+
+    // Where the tip of the arm ended up after the last solve, short of the target when it's out of reach.
+    pub fn end_effector(&self) -> Vec3 {
+        self.end_effector
+    }
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component, MapEntities)]
 pub struct IKArmTarget {
     pub target: Entity
 }
 
-pub struct IKArmPlugin;
+impl MapEntities for IKArmTarget {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.target = entity_mapper.map_entity(self.target);
+    }
+}
+
+// The joints of an arm's skinned mesh, added once they've been instanced, see RigReady. Not saved in scenes,
+// a loaded arm finds its joints again once its skin is back, see LegSkin.
+#[derive(Component, Clone, Debug)]
+pub struct ArmRig {
+    // The joint at the root of each bone, two for the glTF leg. More are solved with ik::solve_chain.
+    pub joints: Vec<Entity>,
+    // A joint at the end of the last bone, when the skin has three joints or more. It gives the last bone its own length.
+    pub tip: Option<Entity>,
+}
+
+impl ArmRig {
+    // Puts how long each bone is in `lengths`, from the joints' translations. Without a tip the last bone is as long
+    // as the one before. Returns the arm's reach, or None while a joint is missing.
+    fn bone_lengths(&self, lengths: &mut Vec<f32>, mut translation: impl FnMut(Entity) -> Option<Vec3>) -> Option<f32> {
+        lengths.clear();
+        lengths.push(translation(self.joints[0])?.distance(translation(self.joints[1])?));
+        for joint in &self.joints[2..] {
+            lengths.push(translation(*joint)?.length());
+        }
+        let last = self.tip.and_then(&mut translation).map_or(*lengths.last().unwrap(), |tip| tip.length());
+        lengths.push(last);
+        Some(lengths.iter().sum())
+    }
+}
+
+// An arm's rig is in place and it can be solved. Legs plant their feet on the ground right after, until then
+// a glTF leg may still be loading and nothing about its pose is known.
+#[derive(Event, Clone, Debug)]
+pub struct RigReady {
+    pub arm: Entity,
+    // How far the arm reaches from its root.
+    pub reach: f32,
+}
+
+pub struct IKArmPlugin {
+    schedule: InternedScheduleLabel,
+}
+
+impl IKArmPlugin {
+    pub fn new() -> Self {
+        Self { schedule: FixedUpdate.intern() }
+    }
+
+    // Runs the systems in another schedule, e.g. FixedUpdate or PostUpdate. See LocomotionSet.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Default for IKArmPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Plugin for IKArmPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (handle_ik, handle_arm_targets));
+        configure_sets(app, self.schedule);
+        app.register_type::<IKArm>()
+        .register_type::<IKArmTarget>()
+        .add_event::<RigReady>()
+        .add_systems(self.schedule, detect_rigs.in_set(LocomotionSet::Input))
+        .add_systems(self.schedule, (handle_arm_targets, handle_ik).chain().in_set(LocomotionSet::IkSolve));
     }
 }
 
+fn detect_rigs(
+    mut commands: Commands,
+    arm_query: Query<Entity, (With<IKArm>, Without<ArmRig>)>,
+    children_query: Query<&Children>,
+    skin_query: Query<&SkinnedMesh>,
+    transform_query: Query<&Transform>,
+    mut ready_events: EventWriter<RigReady>,
+    mut reported: Local<EntityHashSet>,
+) {
+    // Only measured once an arm's rig turns up, not every tick.
+    let mut lengths = Vec::new();
+    for arm_entity in arm_query.iter() {
+        let Some(skinned_mesh) = children_query.iter_descendants(arm_entity).find_map(|child| skin_query.get(child).ok()) else {continue;};
+        let rig = match skinned_mesh.joints.as_slice() {
+            [root, knee] => ArmRig { joints: vec![*root, *knee], tip: None },
+            [joints @ .., tip] if joints.len() >= 2 => ArmRig { joints: joints.to_vec(), tip: Some(*tip) },
+            _ => {
+                if reported.insert(arm_entity) {
+                    error!("IK arm {arm_entity} has a skinned mesh with {} joints, it needs at least two", skinned_mesh.joints.len());
+                }
+                continue;
+            }
+        };
+        // Measured the same way as in handle_ik.
+        let Some(reach) = rig.bone_lengths(&mut lengths, |joint| transform_query.get(joint).ok().map(|transform| transform.translation)) else {continue;};
+        commands.entity(arm_entity).insert(rig);
+        ready_events.send(RigReady { arm: arm_entity, reach });
+    }
+}
+
+// Attacks and grabs steer the arm towards their target themselves.
+type FollowingArms = (Without<Attack>, Without<Grab>);
+
 fn handle_arm_targets(
-    mut arm_query: Query<(&mut IKArm, &IKArmTarget)>,
+    mut arm_query: Query<(&mut IKArm, &IKArmTarget), FollowingArms>,
     target_query: Query<&GlobalTransform>,
 ) {
     for (mut arm, arm_target) in arm_query.iter_mut() {
@@ -30,170 +147,91 @@ fn handle_arm_targets(
     }
 }
 
-fn handle_ik(
-    keys: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-    mut arm_query: Query<(Entity, &IKArm)>,
-    children_query: Query<&Children>,
-    parent_query: Query<(Entity, &SkinnedMesh)>,
-   // mut transform_query: Query<&mut Transform>,
-    mut gtransform_query: Query<&mut GlobalTransform>,
-    mut gizmos: Gizmos,
+// Kept between ticks so solving chains doesn't allocate.
+#[derive(Default)]
+pub(crate) struct ChainScratch {
+    lengths: Vec<f32>,
+    joints: Vec<Vec3>,
+}
+
+pub(crate) fn handle_ik(
+    mut commands: Commands,
+    mut arm_query: Query<(Entity, &mut IKArm, &ArmRig, Option<&LocomotionLod>)>,
+    gtransform_query: Query<&GlobalTransform>,
+    mut debug_gizmos: DebugGizmos,
     mut transform_params: ParamSet<(
         TransformHelper,
         Query<&mut Transform>,
     )>,
+    mut scratch: Local<ChainScratch>,
 ) {
-    for (arm_entity, arm) in arm_query.iter_mut() {
-        for child in children_query.iter_descendants(arm_entity) {
-            let Ok((entity, skinned_mesh)) = parent_query.get(child) else {continue;};
-            let target_position: Vec3 = arm.target;
-            let Ok([knee_transform, transform]) = gtransform_query.get_many([skinned_mesh.joints[1], arm_entity]) else { println!("fuck"); continue; };
-            let mut query = transform_params.p1();
-            let Ok([mut t0, mut t1, mut arm_transform]) = query.get_many_mut([skinned_mesh.joints[0], skinned_mesh.joints[1], arm_entity]) else { println!("fuck"); continue; };
-            let dir = (target_position - transform.translation());
-            let (y, z) = calc_angles(&transform, dir);
-            let d_a: f32 = t0.translation.distance(t1.translation);
-            let d_b: f32 = t0.translation.distance(t1.translation);
-            let mut d_c = dir.length();
-            if (d_c > d_a + d_c) {
-                d_c = d_a + d_c;
-            }
-            let mut a = calc_necessary_angle(d_b, d_c, d_a);
-            let mut b: f32 = calc_necessary_angle(d_a, d_b, d_c);
-            if a.is_nan(){
-                a = 0.;
-            }
-            if b.is_nan() {
-                b = PI;
-            }
-            a = PI/2. - a;
-            b = PI - b;
-            let middle = (transform.translation() + target_position) / 2.;
-            let time_test = time.elapsed_seconds() * 0.5;
-            t0.rotation = Quat::from_euler(EulerRot::XYZ, 0. ,-y , (a - z));
-            t1.rotation = Quat::from_rotation_z(b);
-            let dot = dir.normalize().dot(arm.up);
-            let enabled = {dot > 0.};
-            
-            
-            
-
-            if let Ok(updated_knee_transform) = transform_params.p0().compute_global_transform(skinned_mesh.joints[1]) {
-                let knee_vec = (updated_knee_transform.translation() - middle).normalize();
-                gizmos.line(middle, middle+ knee_vec, Color::WHITE);
-                gizmos.line(middle, middle+ arm.up, Color::WHITE);
-                //println!("{}", knee_vec.dot(arm.up).acos().to_degrees());
-                //println!("{}", knee_vec.angle_between(arm.up).to_degrees());
-                let rotation_arc = Quat::from_rotation_arc( arm.up, knee_vec);
-            // println!("len: {}", rotation_arc.length());
-                let signed_angle = signed_angle_between(knee_vec, arm.up, dir);
-                //let signed_angle2 = knee_vec.angle_between(arm.up) * (knee_vec.cross(arm.up)).signum();
-                let sg = calculate_correction(middle, knee_transform.translation(), arm.up, dir);
-                
-                //println!("Dot: {}", dot);
-             //   println!("SG: {}", sg.to_degrees());
-                
-                let n = 90_f32.to_radians();
-                //transform_params.p1().get_mut(skinned_mesh.joints[0]).unwrap().rotate_axis( Dir3::from_xyz(dir.x, dir.y, dir.z).unwrap(), 0_f32.lerp(signed_angle, 1.)); // we already verified this exists above
-            };
-            if keys.pressed(KeyCode::KeyB) {
-                //println!("FIRST");
-                
-                //t0.rotate(rotation_arc);
-                //let l = t0.looking_to(dir, arm.up);
-                //*t0 = l;
+    for (arm_entity, mut arm, rig, lod) in arm_query.iter_mut() {
+        if lod.is_some_and(|lod| !lod.should_update()) {
+            continue;
+        }
+        let draw_debug = lod.is_none_or(|lod| lod.draws_debug());
+        let target_position: Vec3 = arm.target;
+        if rig.joints.len() > 2 {
+            let iterations = lod.map_or(LodTier::Full, |lod| lod.tier).ik_iterations();
+            if !solve_chain_rig(&mut arm, rig, iterations, &mut transform_params, &mut scratch) {
+                warn!("IK arm {arm_entity} lost its joints, waiting for its rig to be instanced again");
+                commands.entity(arm_entity).remove::<ArmRig>();
             }
-
-
-           // ZE.rotate_axis( Dir3::from_xyz(dir.x, dir.y, dir.z).unwrap(), rot);
-            /*
-            if (enabled) {
-                t0.rotate_axis( Dir3::from_xyz(dir.x, dir.y, dir.z).unwrap(), -signed_angle / 4.);
-                println!("{}", signed_angle.to_degrees());
-            } else {
-                println!("disabled");
-            } */
-            //t0.rotate_axis( Dir3::from_xyz(dir.x, dir.y, dir.z).unwrap(), 0_f32.lerp(signed_angle, 0.01));
-            //let signed_angle2 = knee_vec.angle_between(arm.up) * (knee_vec.cross(arm.up)).signum();
-            //t0.rotate_axis( Dir3::from_xyz(dir.x, dir.y, dir.z).unwrap(), 90_f32.to_radians());
-
-            //arm_transform.rotate_local_axis( Dir3::from_xyz(dir.x, dir.y, dir.z).unwrap(), time_test);
-            /*
-            FIRST UNDERSTAND WHY SIMPLY ROTATING THE WHOLE THING BY 
-            THE ANGLE DIFF DOESN4T WORK, ONLY THEN CAN YOU FIND A FIX
-                The angle works, atleast partly, but it oscillates because basically:
-                    First frame, the arm moves, so it needs ex 90° so it rotates that much
-                    Second frame, t0 rotates again, but since the calculation doesn't see that change, it sees it as perfect, so it does nothing.
-                    SOLUTION: Transform Helper to get the up to date globaltransform for the calculation.
-
-            Solution Plan:
-            Find way to evaluate angle
-                Get GlobalTransform's of [1] joint's position
-                Get the vector from the middle of root to target vector
-                Take the dot product of that and the up vector, minimize to 0
-                FIND A WAY TO COMPUTER WHERE THE KNEE WILL BE
-                TRY 360 SOLUTIONS, TAKE THE BEST ONE, ROTATE BY THAT MUCH
-            Make a function that finds the best rotate_axis value
-            rotate_axis by that amount
-
-            
-            
-            */
+            continue;
+        }
+        let mut query = transform_params.p1();
+        let lower_length = rig.tip.and_then(|tip| query.get(tip).ok()).map(|tip_transform| tip_transform.translation.length());
+        let (Ok(transform), Ok([mut t0, mut t1])) = (gtransform_query.get(arm_entity), query.get_many_mut([rig.joints[0], rig.joints[1]])) else {
+            // Despawned joints, e.g. the leg scene being reloaded. The arm waits for them like it did at first.
+            warn!("IK arm {arm_entity} lost its joints, waiting for its rig to be instanced again");
+            commands.entity(arm_entity).remove::<ArmRig>();
+            continue;
+        };
+        // Without a tip joint there's nothing to measure the lower bone by, it's taken to be as long as the upper one.
+        let d_a: f32 = t0.translation.distance(t1.translation);
+        let d_b: f32 = lower_length.unwrap_or(d_a);
+        let solution = ik::solve_two_bone(transform.translation(), transform.right().into(), target_position, d_a, d_b);
+        let middle = (transform.translation() + target_position) / 2.;
+        t0.rotation = solution.root_rotation;
+        t1.rotation = solution.knee_rotation;
+        arm.end_effector = solution.end_effector;
+
+        if !draw_debug {
+            continue;
+        }
+        if let Ok(updated_knee_transform) = transform_params.p0().compute_global_transform(rig.joints[1]) {
+            let knee_vec = (updated_knee_transform.translation() - middle).normalize();
+            debug_gizmos.line(DebugCategory::IkChain, middle, middle + knee_vec, Color::WHITE);
+            debug_gizmos.line(DebugCategory::PoleVectors, middle, middle + arm.up, Color::WHITE);
         }
     }
 }
 
-fn calc_angles(transform: &GlobalTransform, dir: Vec3) -> (f32, f32) {
-    let y = (-transform.right()).xz().angle_between(dir.xz());
-    let mut inbetween = dir;
-    inbetween.y = 0.;
-    let mut z = dir.angle_between(inbetween);
-    if (dir.y < 0.) {
-        z = -z;
+// Bones past the first two, like those of a procedural leg with more than two. Each joint is turned so its bone,
+// along its local +Y, points at the next joint of the solved chain. Returns false when the joints are gone.
+fn solve_chain_rig(
+    arm: &mut IKArm,
+    rig: &ArmRig,
+    iterations: usize,
+    transform_params: &mut ParamSet<(TransformHelper, Query<&mut Transform>)>,
+    scratch: &mut ChainScratch,
+) -> bool {
+    let ChainScratch { lengths, joints: chain } = scratch;
+    if rig.bone_lengths(lengths, |joint| transform_params.p1().get(joint).ok().map(|transform| transform.translation)).is_none() {
+        return false;
     }
-    return (y, z);
-}
-
-fn calc_necessary_angle(a: f32, b: f32, c: f32) -> f32 {
-    let top_part = a.powf(2.) + b.powf(2.) - c.powf(2.);
-    let bottom_part = 2. * a * b;
-    let result = (top_part / bottom_part).acos();
-    return result;
-}
-
-fn find_best() -> f32 {
-    let mut best_i = 0;
-    let mut best = 0.;
-    for i in 0..359 {
-        let result = 0.;
-        if (result < best) {
-            best = result;
-            best_i = i;
-        }
+    let Ok(root) = transform_params.p0().compute_global_transform(rig.joints[0]) else {return false;};
+    let mut query = transform_params.p1();
+    let Ok(root_transform) = query.get(rig.joints[0]) else {return false;};
+    // What the root joint's rotation is relative to.
+    let mut parent_rotation = root.compute_transform().rotation * root_transform.rotation.inverse();
+    ik::solve_chain_from_arc(root.translation(), arm.target, lengths, arm.up, CHAIN_TOLERANCE, iterations, chain);
+    for (joint, bone) in rig.joints.iter().zip(chain.windows(2)) {
+        let Ok(mut transform) = query.get_mut(*joint) else {return false;};
+        let Some(dir) = (bone[1] - bone[0]).try_normalize() else {continue;};
+        transform.rotation = Quat::from_rotation_arc(Vec3::Y, parent_rotation.inverse() * dir);
+        parent_rotation *= transform.rotation;
     }
-    return best_i as f32;
+    arm.end_effector = *chain.last().unwrap();
+    true
 }
-
-fn calculate_correction(middle: Vec3, knee_position: Vec3, up :Vec3, dir: Vec3) -> f32 {
-    let knee_vec = (knee_position - middle).normalize();
-    let signed_angle = signed_angle_between(knee_vec, up, -dir);
-    return signed_angle;
-}
-
-fn signed_angle_between(a: Vec3, b: Vec3, normal: Vec3) -> f32 {
-    let v1 = a.normalize();
-    let v2 = b.normalize();
-
-    let dot_product = v1.dot(v2).clamp(-1.0, 1.0); // Clamp to avoid numerical errors
-    let unsigned_angle = dot_product.acos(); // This is the unsigned angle
-
-    let cross_product = v1.cross(v2);
-    let sign = cross_product.dot(normal.normalize());
-
-    if sign < 0.0 {
-        -unsigned_angle
-    } else {
-        unsigned_angle
-    }
-}
\ No newline at end of file
@@ -0,0 +1,169 @@
+use bevy::{ecs::schedule::{InternedScheduleLabel, ScheduleLabel}, prelude::*};
+
+use crate::{inspect::ReadOnly, leg::{IKLeg, LegCreature}, schedule::{configure_sets, LocomotionSet}, IKArm::{IKArm, IKArmTarget}};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Reflect)]
+pub enum AttackPhase {
+    WindUp,
+    Strike,
+    Recovery,
+}
+
+// Insert together with an IKArmTarget pointing at the victim, e.g. on LegCreature::front_leg(). The leg leaves the
+// gait for the duration of the attack, and both components are removed once it has recovered.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct Attack {
+    pub wind_up: f32,
+    pub strike: f32,
+    pub recovery: f32,
+    // How far from its resting position the foot can strike.
+    pub reach: f32,
+    // How high the foot is raised while winding up.
+    pub lift: f32,
+    // Distance from the target at which the strike counts as a hit.
+    pub hit_tolerance: f32,
+    #[reflect(@ReadOnly)]
+    elapsed: f32,
+    #[reflect(@ReadOnly)]
+    rest_offset: Option<Vec3>,
+    // Where the strike left the foot, it recovers from there.
+    #[reflect(@ReadOnly)]
+    struck: Vec3,
+}
+
+impl Default for Attack {
+    fn default() -> Self {
+        Self { wind_up: 0.25, strike: 0.1, recovery: 0.3, reach: 0.6, lift: 0.25, hit_tolerance: 0.15, elapsed: 0., rest_offset: None, struck: Vec3::ZERO }
+    }
+}
+
+impl Attack {
+    pub fn phase(&self) -> AttackPhase {
+        if self.elapsed < self.wind_up {
+            AttackPhase::WindUp
+        } else if self.elapsed < self.wind_up + self.strike {
+            AttackPhase::Strike
+        } else {
+            AttackPhase::Recovery
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.wind_up + self.strike + self.recovery
+    }
+}
+
+// Sent on the frame the strike connects.
+#[derive(Event, Clone, Debug)]
+pub struct AttackHit {
+    pub leg: Entity,
+    pub target: Entity,
+    pub position: Vec3,
+}
+
+impl LegCreature {
+    // The leg furthest forward, the natural one to strike with.
+    pub fn front_leg(&self) -> Option<Entity> {
+        self.legs_info.iter()
+            .max_by(|a, b| a.1.z.total_cmp(&b.1.z))
+            .map(|(leg_entity, _)| *leg_entity)
+    }
+}
+
+pub struct AttackPlugin {
+    schedule: InternedScheduleLabel,
+}
+
+impl AttackPlugin {
+    pub fn new() -> Self {
+        Self { schedule: FixedUpdate.intern() }
+    }
+
+    // Runs the systems in another schedule, e.g. FixedUpdate or PostUpdate. See LocomotionSet.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Default for AttackPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for AttackPlugin {
+    fn build(&self, app: &mut App) {
+        configure_sets(app, self.schedule);
+        app.register_type::<Attack>()
+        .add_event::<AttackHit>()
+        .add_systems(self.schedule, handle_attacks.in_set(LocomotionSet::FootPlacement));
+    }
+}
+
+type AttackingLeg = (Entity, &'static GlobalTransform, &'static mut IKArm, &'static mut Attack, &'static IKArmTarget, Option<&'static mut IKLeg>);
+
+fn handle_attacks(
+    mut commands: Commands,
+    mut attack_query: Query<AttackingLeg>,
+    target_query: Query<&GlobalTransform>,
+    mut hit_events: EventWriter<AttackHit>,
+    time: Res<Time>,
+) {
+    for (leg_entity, transform, mut arm, mut attack, arm_target, leg) in attack_query.iter_mut() {
+        let origin = transform.translation();
+        let rest_offset = *attack.rest_offset.get_or_insert(arm.target - origin);
+        if let Some(mut leg) = leg {
+            leg.interrupt_step();
+        }
+        let rest = origin + rest_offset;
+        let Ok(target_transform) = target_query.get(arm_target.target) else {
+            commands.entity(leg_entity).remove::<(Attack, IKArmTarget)>();
+            continue;
+        };
+        let target_pos = target_transform.translation();
+        let toward = (target_pos - rest).normalize_or_zero();
+        // Pulled back and up, away from the target.
+        let cocked = rest + Vec3::Y * attack.lift - toward * attack.lift * 0.5;
+
+        let previous_phase = attack.phase();
+        attack.elapsed += time.delta_seconds();
+        match attack.phase() {
+            AttackPhase::WindUp => {
+                let t = smoothstep(progress(attack.elapsed, attack.wind_up));
+                arm.target = rest.lerp(cocked, t);
+            }
+            AttackPhase::Strike => {
+                let contact = rest + (target_pos - rest).clamp_length_max(attack.reach);
+                // Accelerates into the target.
+                let t = progress(attack.elapsed - attack.wind_up, attack.strike);
+                arm.target = cocked.lerp(contact, t * t);
+            }
+            AttackPhase::Recovery => {
+                if previous_phase != AttackPhase::Recovery {
+                    // Where the strike actually took the foot, the IK may not have reached the target.
+                    let foot = arm.end_effector();
+                    if foot.distance(target_pos) <= attack.hit_tolerance {
+                        hit_events.send(AttackHit { leg: leg_entity, target: arm_target.target, position: foot });
+                    }
+                    attack.struck = arm.target;
+                }
+                let t = smoothstep(progress(attack.elapsed - attack.wind_up - attack.strike, attack.recovery));
+                arm.target = attack.struck.lerp(rest, t);
+                if attack.elapsed >= attack.duration() {
+                    commands.entity(leg_entity).remove::<(Attack, IKArmTarget)>();
+                }
+            }
+        }
+    }
+}
+
+// How far through a phase of `duration` seconds it is, phases can be zero long.
+fn progress(elapsed: f32, duration: f32) -> f32 {
+    if duration > 0. { (elapsed / duration).clamp(0., 1.) } else { 1. }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3. - 2. * t)
+}
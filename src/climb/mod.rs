@@ -0,0 +1,128 @@
+use bevy::{ecs::system::{StaticSystemParam, SystemParam}, prelude::*};
+
+use crate::{foothold::{FootholdSearch, SurfaceQuery}, ground::GroundQuery, jump::Airborne, leg::LegCreature, lod::LocomotionLod, ragdoll::Crumple};
+
+// Clearance kept above ledges and below probe origins.
+const MARGIN: f32 = 0.05;
+
+// What lies ahead of a walking creature, as planned by plan_climbs.
+#[derive(Copy, Clone, PartialEq, Default, Debug, Reflect)]
+pub enum Ledge {
+    #[default] None,
+    // A ledge to climb onto, `height` above the current ground and `distance` ahead.
+    Up { height: f32, distance: f32 },
+    // A drop to step down.
+    Down { height: f32, distance: f32 },
+    // Taller than the creature can climb, or a drop it won't take. The creature stops in front of it.
+    Blocked { height: f32, distance: f32 },
+}
+
+// How a creature type deals with obstacles, optional on a LegCreature.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct ClimbSettings {
+    pub max_climb_height: f32,
+    pub max_step_down: f32,
+    // How far ahead of the body to look for ledges.
+    pub probe_distance: f32,
+    // How strongly the body pitches towards a ledge.
+    pub pitch: f32,
+    // Stop at drops deeper than max_step_down instead of walking off them and falling.
+    pub block_drops: bool,
+}
+
+impl Default for ClimbSettings {
+    fn default() -> Self {
+        Self { max_climb_height: 0.4, max_step_down: 0.4, probe_distance: 0.4, pitch: 0.5, block_drops: false }
+    }
+}
+
+impl Ledge {
+    // The foothold search a creature should use with this ledge ahead, so feet can reach up onto it or down past it.
+    pub fn adjust_search(self, search: &FootholdSearch) -> FootholdSearch {
+        let mut search = search.clone();
+        match self {
+            Ledge::Up { height, .. } => search.probe_height = search.probe_height.max(height + MARGIN * 2.),
+            Ledge::Down { height, .. } => search.max_drop = search.max_drop.max(height + MARGIN),
+            Ledge::None | Ledge::Blocked { .. } => {}
+        }
+        search
+    }
+}
+
+type ClimbingCreature = (&'static GlobalTransform, &'static mut LegCreature, Option<&'static ClimbSettings>, Option<&'static LocomotionLod>);
+
+pub(crate) fn plan_climbs<G: SystemParam>(
+    mut creature_query: Query<ClimbingCreature, (Without<Crumple>, Without<Airborne>)>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let default_settings = ClimbSettings::default();
+    for (transform, mut leg_creature, settings, lod) in creature_query.iter_mut() {
+        if lod.is_some_and(|lod| !lod.should_update()) {
+            continue;
+        }
+        let settings = settings.unwrap_or(&default_settings);
+        let body = transform.translation();
+        let up = leg_creature.up();
+        let move_dir = (transform.transform_point(leg_creature.target_offset()) - body).reject_from_normalized(up).normalize_or_zero();
+        // Once stopped, looks a little further so shuffling back from the obstacle doesn't set it walking again.
+        let reach = if matches!(leg_creature.ledge, Ledge::Blocked { .. }) { settings.probe_distance + MARGIN * 2. } else { settings.probe_distance };
+        let ledge = if move_dir == Vec3::ZERO {
+            Ledge::None
+        } else {
+            find_ledge(&mut *ground, &surfaces, leg_creature.floor(body), up, move_dir, reach, settings)
+        };
+        // Nose up towards ledges above, down towards drops.
+        let target_lean = match ledge {
+            Ledge::Up { height, distance } => -move_dir * height / distance.max(MARGIN) * settings.pitch,
+            Ledge::Down { height, distance } => move_dir * height / distance.max(MARGIN) * settings.pitch,
+            Ledge::None | Ledge::Blocked { .. } => Vec3::ZERO,
+        };
+        leg_creature.ledge = ledge;
+        leg_creature.lean = leg_creature.lean.lerp(target_lean, 0.1);
+    }
+}
+
+fn find_ledge(
+    ground: &mut impl GroundQuery,
+    surfaces: &SurfaceQuery,
+    floor: Vec3,
+    up: Vec3,
+    move_dir: Vec3,
+    reach: f32,
+    settings: &ClimbSettings,
+) -> Ledge {
+    let filter = |entity: Entity| !surfaces.is_creature_part(entity);
+    let (Ok(forward), Ok(down)) = (Dir3::new(move_dir), Dir3::new(-up)) else {return Ledge::None;};
+
+    // Something in the way just above the ground, look down onto it from above the highest climbable ledge.
+    let ahead = Ray3d { origin: floor + up * MARGIN, direction: forward };
+    if let Some(wall) = ground.cast_ground_ray(ahead, reach, &filter) {
+        let top_origin = wall.position + move_dir * MARGIN + up * (settings.max_climb_height + MARGIN);
+        let top = ground.cast_ground_ray(Ray3d { origin: top_origin, direction: down }, settings.max_climb_height + MARGIN, &filter);
+        let height = top.map_or(f32::INFINITY, |top| (top.position - floor).dot(up));
+        // A hit right at the origin means the probe started inside something taller.
+        if top.is_some_and(|top| top.distance > MARGIN * 0.5) && height <= settings.max_climb_height {
+            return Ledge::Up { height, distance: wall.distance };
+        }
+        return Ledge::Blocked { height, distance: wall.distance };
+    }
+
+    // Nothing in the way, check the ground doesn't fall away.
+    let drop_origin = floor + move_dir * reach + up * MARGIN;
+    let drop_reach = MARGIN + settings.max_step_down * 2.;
+    let depth = ground.cast_ground_ray(Ray3d { origin: drop_origin, direction: down }, drop_reach, &filter)
+        .map_or(f32::INFINITY, |hit| (floor - hit.position).dot(up));
+    if depth <= MARGIN {
+        Ledge::None
+    } else if depth <= settings.max_step_down {
+        Ledge::Down { height: depth, distance: reach }
+    } else if settings.block_drops {
+        Ledge::Blocked { height: -depth, distance: reach }
+    } else {
+        Ledge::None
+    }
+}
@@ -0,0 +1,156 @@
+#[cfg(not(feature = "debug"))]
+use std::marker::PhantomData;
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DebugCategory {
+    Raycasts,
+    StepTargets,
+    BodyPlane,
+    IkChain,
+    PoleVectors,
+    GaitPhases,
+}
+
+impl DebugCategory {
+    pub const ALL: [DebugCategory; 6] = [
+        DebugCategory::Raycasts,
+        DebugCategory::StepTargets,
+        DebugCategory::BodyPlane,
+        DebugCategory::IkChain,
+        DebugCategory::PoleVectors,
+        DebugCategory::GaitPhases,
+    ];
+
+    pub fn toggle_key(self) -> KeyCode {
+        match self {
+            DebugCategory::Raycasts => KeyCode::F1,
+            DebugCategory::StepTargets => KeyCode::F2,
+            DebugCategory::BodyPlane => KeyCode::F3,
+            DebugCategory::IkChain => KeyCode::F4,
+            DebugCategory::PoleVectors => KeyCode::F5,
+            DebugCategory::GaitPhases => KeyCode::F6,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct DebugDrawConfig {
+    pub raycasts: bool,
+    pub step_targets: bool,
+    pub body_plane: bool,
+    pub ik_chain: bool,
+    pub pole_vectors: bool,
+    pub gait_phases: bool,
+}
+
+impl Default for DebugDrawConfig {
+    fn default() -> Self {
+        Self { raycasts: true, step_targets: true, body_plane: false, ik_chain: true, pole_vectors: true, gait_phases: false }
+    }
+}
+
+impl DebugDrawConfig {
+    fn flag(&mut self, category: DebugCategory) -> &mut bool {
+        match category {
+            DebugCategory::Raycasts => &mut self.raycasts,
+            DebugCategory::StepTargets => &mut self.step_targets,
+            DebugCategory::BodyPlane => &mut self.body_plane,
+            DebugCategory::IkChain => &mut self.ik_chain,
+            DebugCategory::PoleVectors => &mut self.pole_vectors,
+            DebugCategory::GaitPhases => &mut self.gait_phases,
+        }
+    }
+
+    pub fn enabled(&self, category: DebugCategory) -> bool {
+        match category {
+            DebugCategory::Raycasts => self.raycasts,
+            DebugCategory::StepTargets => self.step_targets,
+            DebugCategory::BodyPlane => self.body_plane,
+            DebugCategory::IkChain => self.ik_chain,
+            DebugCategory::PoleVectors => self.pole_vectors,
+            DebugCategory::GaitPhases => self.gait_phases,
+        }
+    }
+
+    pub fn toggle(&mut self, category: DebugCategory) {
+        let flag = self.flag(category);
+        *flag = !*flag;
+    }
+}
+
+pub struct DebugDrawPlugin;
+
+impl Plugin for DebugDrawPlugin {
+    #[cfg_attr(not(feature = "debug"), allow(unused_variables))]
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "debug")]
+        app.init_resource::<DebugDrawConfig>()
+        .add_systems(Update, toggle_debug_draw);
+    }
+}
+
+#[cfg(feature = "debug")]
+fn toggle_debug_draw(
+    mut config: ResMut<DebugDrawConfig>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    for category in DebugCategory::ALL {
+        if keys.just_pressed(category.toggle_key()) {
+            config.toggle(category);
+        }
+    }
+}
+
+// Gizmos filtered by DebugDrawConfig. Without the `debug` feature this holds nothing and every call is a no-op,
+// so locomotion systems can draw unconditionally and it compiles out of shipping builds.
+#[cfg(feature = "debug")]
+#[derive(SystemParam)]
+pub struct DebugGizmos<'w, 's> {
+    gizmos: Gizmos<'w, 's>,
+    config: Option<Res<'w, DebugDrawConfig>>,
+}
+
+#[cfg(not(feature = "debug"))]
+#[derive(SystemParam)]
+pub struct DebugGizmos<'w, 's> {
+    marker: PhantomData<(&'w (), &'s ())>,
+}
+
+#[cfg(feature = "debug")]
+impl<'w, 's> DebugGizmos<'w, 's> {
+    pub fn enabled(&self, category: DebugCategory) -> bool {
+        self.config.as_ref().is_some_and(|config| config.enabled(category))
+    }
+
+    pub fn gizmos(&mut self, category: DebugCategory) -> Option<&mut Gizmos<'w, 's>> {
+        if self.enabled(category) { Some(&mut self.gizmos) } else { None }
+    }
+}
+
+#[cfg(not(feature = "debug"))]
+impl<'w, 's> DebugGizmos<'w, 's> {
+    #[inline(always)]
+    pub fn enabled(&self, _category: DebugCategory) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    pub fn gizmos(&mut self, _category: DebugCategory) -> Option<&mut Gizmos<'w, 's>> {
+        None
+    }
+}
+
+impl<'w, 's> DebugGizmos<'w, 's> {
+    pub fn line(&mut self, category: DebugCategory, start: Vec3, end: Vec3, color: impl Into<Color>) {
+        if let Some(gizmos) = self.gizmos(category) {
+            gizmos.line(start, end, color);
+        }
+    }
+
+    pub fn sphere(&mut self, category: DebugCategory, position: Vec3, radius: f32, color: impl Into<Color>) {
+        if let Some(gizmos) = self.gizmos(category) {
+            gizmos.sphere(position, Quat::IDENTITY, radius, color);
+        }
+    }
+}
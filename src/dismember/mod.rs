@@ -0,0 +1,158 @@
+use std::f32::consts::TAU;
+use bevy::{ecs::system::{StaticSystemParam, SystemParam}, prelude::*};
+
+use crate::{foothold::SurfaceQuery, ground::GroundQuery, health::{Dead, DeathPose}, ik, inspect::ReadOnly, leg::{IKLeg, LegCreature, LegSide}, lod::LocomotionLod, IKArm::IKArm};
+
+pub(crate) const GRAVITY: f32 = 9.81;
+
+// Takes a leg out of the gait while it stays attached, it just hangs under the hip. Remove it to walk on the leg again.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct DisabledLeg;
+
+// Insert on a leg to shoot it off. The leg leaves its creature, falls with the given velocity and rests where it lands.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct SeveredLeg {
+    #[reflect(@ReadOnly)]
+    velocity: Vec3,
+    #[reflect(@ReadOnly)]
+    foot_offset: Vec3,
+    #[reflect(@ReadOnly)]
+    landed: bool,
+}
+
+impl SeveredLeg {
+    pub fn new(velocity: Vec3) -> Self {
+        Self { velocity, ..default() }
+    }
+}
+
+// How a creature copes with losing legs, optional on a LegCreature.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct LegLossResponse {
+    // Fraction of the body height lost per missing leg.
+    pub sag_per_leg: f32,
+    // Fewer walking legs than this and the creature collapses.
+    pub min_support_legs: usize,
+    // It also collapses when the body isn't over the feet it has left, and this far outside them counts as over them.
+    // Keeps three legs of four standing with the body right on the line between two of them.
+    pub support_margin: f32,
+    // Body height above the remaining feet once collapsed.
+    pub collapsed_height: f32,
+}
+
+impl Default for LegLossResponse {
+    fn default() -> Self {
+        Self { sag_per_leg: 0.15, min_support_legs: 3, support_margin: 0.05, collapsed_height: 0.05 }
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct LegLost {
+    pub creature: Entity,
+    pub leg: Entity,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct CreatureCollapsed {
+    pub creature: Entity,
+}
+
+pub(crate) fn detach_severed_legs(
+    mut commands: Commands,
+    mut severed_query: Query<(Entity, &Transform, &IKArm, &mut SeveredLeg), Added<SeveredLeg>>,
+) {
+    for (leg_entity, transform, arm, mut severed) in severed_query.iter_mut() {
+        severed.foot_offset = arm.target - transform.translation;
+        commands.entity(leg_entity).remove::<(IKLeg, LocomotionLod, DisabledLeg)>();
+    }
+}
+
+type ReplannedCreature = (Entity, &'static GlobalTransform, &'static mut LegCreature, Option<&'static LegLossResponse>, Option<&'static Dead>);
+
+// Replans the gait whenever the number of walking legs changes, lost legs have already left legs_info by then (see
+// LegOf). That's also when the feet left are checked for whether they can still hold the body up, the ones stepping
+// at the time count where they're headed. Dead creatures that should collapse go down the same way as ones that
+// ran out of legs.
+pub(crate) fn replan_gaits(
+    mut creature_query: Query<ReplannedCreature>,
+    mut leg_query: Query<(&mut IKLeg, &IKArm, Has<DisabledLeg>), Without<SeveredLeg>>,
+    mut collapsed_events: EventWriter<CreatureCollapsed>,
+) {
+    let default_response = LegLossResponse::default();
+    for (creature_entity, transform, mut leg_creature, response, dead) in creature_query.iter_mut() {
+        let response = response.unwrap_or(&default_response);
+        let leg_creature = &mut *leg_creature;
+        let walking = leg_creature.legs_info.iter()
+            .filter(|(leg_entity, _)| leg_query.get(*leg_entity).is_ok_and(|(_, _, disabled)| !disabled))
+            .count();
+        if walking != leg_creature.walking_legs {
+            let feet: Vec<Vec3> = leg_creature.legs_info.iter()
+                .filter_map(|(leg_entity, _)| leg_query.get(*leg_entity).ok())
+                .filter(|(_, _, disabled)| !disabled)
+                .map(|(_, arm, _)| arm.target)
+                .collect();
+            leg_creature.supported = ik::support_polygon_contains(&feet, transform.translation(), leg_creature.up(), response.support_margin);
+        }
+        let collapsed = walking < response.min_support_legs || !leg_creature.supported || dead.is_some_and(|dead| dead.pose != DeathPose::Stand);
+        if walking == leg_creature.walking_legs && collapsed == leg_creature.collapsed {
+            continue;
+        }
+        leg_creature.walking_legs = walking;
+
+        // Alternate the groups going around the body, for four legs that's the usual diagonal pairs.
+        let mut ring: Vec<(f32, Entity)> = leg_creature.legs_info.iter()
+            .filter(|(leg_entity, _)| leg_query.get(*leg_entity).is_ok_and(|(_, _, disabled)| !disabled))
+            .map(|(leg_entity, offset)| (offset.z.atan2(offset.x).rem_euclid(TAU), *leg_entity))
+            .collect();
+        ring.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (i, (_, leg_entity)) in ring.iter().enumerate() {
+            let Ok((mut leg, _, _)) = leg_query.get_mut(*leg_entity) else {continue;};
+            leg.leg_side = if i % 2 == 0 { LegSide::Left } else { LegSide::Right };
+        }
+
+        let was_collapsed = leg_creature.collapsed;
+        leg_creature.collapsed = collapsed;
+        if leg_creature.collapsed {
+            leg_creature.height_scale = response.collapsed_height / leg_creature.target_height.max(f32::EPSILON);
+            if !was_collapsed {
+                collapsed_events.send(CreatureCollapsed { creature: creature_entity });
+            }
+        } else {
+            let missing = leg_creature.original_legs.saturating_sub(walking);
+            leg_creature.height_scale = (1. - response.sag_per_leg * missing as f32).max(0.);
+        }
+    }
+}
+
+pub(crate) fn drop_severed_legs<G: SystemParam>(
+    mut severed_query: Query<(&mut Transform, &mut IKArm, &mut SeveredLeg)>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+    time: Res<Time>,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let delta = time.delta_seconds();
+    for (mut transform, mut arm, mut severed) in severed_query.iter_mut() {
+        // Foot curls halfway in towards the stump.
+        arm.target = transform.translation + severed.foot_offset * 0.5;
+        if severed.landed {
+            continue;
+        }
+        severed.velocity += Vec3::NEG_Y * GRAVITY * delta;
+        let motion = severed.velocity * delta;
+        let Ok(direction) = Dir3::new(motion) else {continue;};
+        let ray = Ray3d { origin: transform.translation, direction };
+        match ground.cast_ground_ray(ray, motion.length(), &|entity| !surfaces.is_creature_part(entity)) {
+            Some(hit) => {
+                transform.translation = hit.position + hit.normal * 0.05;
+                severed.landed = true;
+            }
+            None => transform.translation += motion,
+        }
+        arm.target = transform.translation + severed.foot_offset * 0.5;
+    }
+}
@@ -0,0 +1,331 @@
+use std::f32::consts::TAU;
+use bevy::{ecs::system::SystemParam, gltf::{GltfExtras, GltfMaterialExtras, GltfMeshExtras}, prelude::*};
+
+use crate::{dismember::SeveredLeg, grab::Carried, ground::{GroundHit, GroundQuery}, leg::{IKLeg, LegCreature}};
+
+const MAX_PROBES: usize = 16;
+
+// Put on map entities (or a parent of them) to tell the step search what it's standing on. Can also come from
+// a `"surface"` custom property in Blender on an object, mesh or material, which ends up in the glTF extras,
+// e.g. `surface = "slippery"`.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug, Reflect)]
+#[reflect(Component)]
+pub enum SurfaceTag {
+    #[default] Walkable,
+    Unwalkable,
+    Slippery,
+    Sticky,
+    Climbable,
+}
+
+impl SurfaceTag {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "walkable" => Some(SurfaceTag::Walkable),
+            "unwalkable" => Some(SurfaceTag::Unwalkable),
+            "slippery" | "ice" => Some(SurfaceTag::Slippery),
+            "sticky" => Some(SurfaceTag::Sticky),
+            "climbable" => Some(SurfaceTag::Climbable),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SurfaceRule {
+    pub allows_foothold: bool,
+    // Lets feet plant on surfaces steeper than FootholdSearch::max_slope, i.e. walls.
+    pub ignores_slope_limit: bool,
+    pub step_length_scale: f32,
+    pub step_duration_scale: f32,
+    // Added to the foothold score, so creatures prefer cheaper surfaces when they have a choice.
+    pub cost: f32,
+}
+
+impl Default for SurfaceRule {
+    fn default() -> Self {
+        Self { allows_foothold: true, ignores_slope_limit: false, step_length_scale: 1., step_duration_scale: 1., cost: 0. }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SurfaceRules {
+    pub walkable: SurfaceRule,
+    pub unwalkable: SurfaceRule,
+    pub slippery: SurfaceRule,
+    pub sticky: SurfaceRule,
+    pub climbable: SurfaceRule,
+}
+
+impl Default for SurfaceRules {
+    fn default() -> Self {
+        Self {
+            walkable: SurfaceRule::default(),
+            unwalkable: SurfaceRule { allows_foothold: false, ..default() },
+            slippery: SurfaceRule { step_length_scale: 0.5, cost: 0.5, ..default() },
+            sticky: SurfaceRule { step_duration_scale: 1.5, cost: 0.25, ..default() },
+            climbable: SurfaceRule { ignores_slope_limit: true, ..default() },
+        }
+    }
+}
+
+impl SurfaceRules {
+    pub fn get(&self, tag: SurfaceTag) -> &SurfaceRule {
+        match tag {
+            SurfaceTag::Walkable => &self.walkable,
+            SurfaceTag::Unwalkable => &self.unwalkable,
+            SurfaceTag::Slippery => &self.slippery,
+            SurfaceTag::Sticky => &self.sticky,
+            SurfaceTag::Climbable => &self.climbable,
+        }
+    }
+}
+
+// How a creature looks for footholds, optional on a LegCreature.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct FootholdSearch {
+    pub probe_count: usize,
+    pub probe_radius: f32,
+    pub probe_height: f32,
+    pub max_drop: f32,
+    pub max_slope: f32,
+    pub slope_weight: f32,
+    pub distance_weight: f32,
+    pub stability_weight: f32,
+}
+
+impl Default for FootholdSearch {
+    fn default() -> Self {
+        Self {
+            probe_count: 6,
+            probe_radius: 0.08,
+            probe_height: 0.5,
+            max_drop: 0.5,
+            max_slope: 50_f32.to_radians(),
+            slope_weight: 1.,
+            distance_weight: 1.,
+            stability_weight: 2.,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Foothold {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub surface: SurfaceTag,
+}
+
+#[derive(Copy, Clone)]
+struct Candidate {
+    hit: GroundHit,
+    slope: f32,
+    surface: SurfaceTag,
+}
+
+type CreaturePart = Or<(With<LegCreature>, With<IKLeg>, With<SeveredLeg>, With<Carried>)>;
+
+#[derive(SystemParam)]
+pub struct SurfaceQuery<'w, 's> {
+    parent_query: Query<'w, 's, &'static Parent>,
+    creature_query: Query<'w, 's, (), CreaturePart>,
+    surface_query: Query<'w, 's, &'static SurfaceTag>,
+    rules: Res<'w, SurfaceRules>,
+}
+
+impl<'w, 's> SurfaceQuery<'w, 's> {
+    pub fn is_creature_part(&self, entity: Entity) -> bool {
+        self.creature_query.contains(entity) || self.parent_query.iter_ancestors(entity).any(|ancestor| self.creature_query.contains(ancestor))
+    }
+
+    // Tags are looked up on the hit mesh first, then up its hierarchy, so tagging a glTF node covers its meshes.
+    pub fn surface_tag(&self, entity: Option<Entity>) -> SurfaceTag {
+        let Some(entity) = entity else {return SurfaceTag::default();};
+        std::iter::once(entity)
+            .chain(self.parent_query.iter_ancestors(entity))
+            .find_map(|entity| self.surface_query.get(entity).ok().copied())
+            .unwrap_or_default()
+    }
+
+    pub fn rule(&self, tag: SurfaceTag) -> &SurfaceRule {
+        self.rules.get(tag)
+    }
+
+    // Whether a foot may go on this hit, walls only count when their surface is climbable.
+    fn accepts(&self, hit: &GroundHit, up: Vec3, max_slope: f32) -> Option<(SurfaceTag, f32)> {
+        let surface = self.surface_tag(hit.entity);
+        let rule = self.rule(surface);
+        let slope = hit.normal.angle_between(up);
+        if !rule.allows_foothold || slope.is_nan() || (slope > max_slope && !rule.ignores_slope_limit) {
+            return None;
+        }
+        Some((surface, slope))
+    }
+}
+
+type Extras = (Entity, Option<&'static GltfExtras>, Option<&'static GltfMeshExtras>, Option<&'static GltfMaterialExtras>);
+// Entities that have just been given custom properties and aren't tagged by hand.
+type NewExtras = (Or<(Added<GltfExtras>, Added<GltfMeshExtras>, Added<GltfMaterialExtras>)>, Without<SurfaceTag>);
+
+// Tags map entities from their Blender custom properties once the glTF scene has spawned them. Object properties
+// end up on the node, mesh and material ones on each primitive. A mesh's tag wins over its material's, which is
+// shared with every other mesh made of it.
+pub(crate) fn import_surface_tags(
+    mut commands: Commands,
+    extras_query: Query<Extras, NewExtras>,
+) {
+    for (entity, node, mesh, material) in extras_query.iter() {
+        let sources = [node.map(|extras| &extras.value), mesh.map(|extras| &extras.value), material.map(|extras| &extras.value)];
+        let Some(surface) = sources.into_iter().flatten()
+            .find_map(|json| extras_value(json, "surface").and_then(|name| SurfaceTag::from_name(&name))) else {continue;};
+        commands.entity(entity).insert(surface);
+    }
+}
+
+// Extras are a JSON object of custom properties, this pulls out one string value.
+fn extras_value(json: &str, key: &str) -> Option<String> {
+    let extras: serde_json::Value = serde_json::from_str(json).ok()?;
+    Some(extras.get(key)?.as_str()?.to_owned())
+}
+
+// First hit along the ray that isn't part of a creature, ours or anyone else's.
+fn probe(
+    ground: &mut impl GroundQuery,
+    surfaces: &SurfaceQuery,
+    ray: Ray3d,
+    max_distance: f32,
+    gizmos: &mut Option<&mut Gizmos>,
+) -> Option<GroundHit> {
+    let hit = ground.cast_ground_ray(ray, max_distance, &|entity| !surfaces.is_creature_part(entity));
+    if let Some(gizmos) = gizmos {
+        let end = hit.map_or(ray.get_point(max_distance), |hit| hit.position);
+        gizmos.line(ray.origin, end, if hit.is_some() { Color::linear_rgb(0., 1., 0.) } else { Color::linear_rgb(1., 0., 1.) });
+    }
+    hit
+}
+
+// Where one foot wants to go, and how hard to look around it.
+#[derive(Copy, Clone)]
+pub struct FootholdRequest<'a> {
+    pub search: &'a FootholdSearch,
+    // The creature's body.
+    pub transform: &'a GlobalTransform,
+    pub up: Vec3,
+    pub desired_pos: Vec3,
+    // Rays the search may cast, see LodTier::ray_count.
+    pub ray_budget: usize,
+}
+
+// Probes the desired position and a ring around it, and picks the flattest, closest, most stable walkable hit.
+// Falls back to the old two-ray search when every probe is rejected and the budget has rays left for it, and to
+// None (keep the current foothold) when that finds nothing either.
+pub fn find_foothold(
+    ground: &mut impl GroundQuery,
+    surfaces: &SurfaceQuery,
+    request: &FootholdRequest,
+    mut gizmos: Option<&mut Gizmos>,
+) -> Option<Foothold> {
+    let FootholdRequest { search, up, desired_pos, ray_budget, .. } = *request;
+    if ray_budget == 0 {
+        return None;
+    }
+    let ring_size = search.probe_count.min(MAX_PROBES - 1);
+    let ring_probes = ring_size.min(ray_budget - 1);
+    // With a reduced budget, spread the probes we can afford evenly around the ring.
+    let stride = ring_size.checked_div(ring_probes).unwrap_or(1);
+    let (tangent, bitangent) = up.any_orthonormal_pair();
+
+    let mut candidates: [Option<Candidate>; MAX_PROBES] = [None; MAX_PROBES];
+    for (i, slot) in candidates.iter_mut().enumerate().take(ring_probes + 1) {
+        let point = if i == 0 {
+            desired_pos
+        } else {
+            let angle = TAU * ((i - 1) * stride) as f32 / ring_size as f32;
+            desired_pos + (tangent * angle.cos() + bitangent * angle.sin()) * search.probe_radius
+        };
+        let Ok(direction) = Dir3::new(-up) else {return None;};
+        let ray = Ray3d { origin: point + up * search.probe_height, direction };
+        let Some(hit) = probe(ground, surfaces, ray, search.probe_height + search.max_drop, &mut gizmos) else {continue;};
+        let Some((surface, slope)) = surfaces.accepts(&hit, up, search.max_slope) else {continue;};
+        *slot = Some(Candidate { hit, slope, surface });
+    }
+
+    let best = candidates.iter().flatten()
+        .map(|candidate| (candidate, score(candidate, &candidates, search, surfaces, desired_pos)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(candidate, _)| Foothold { position: candidate.hit.position, normal: candidate.hit.normal, surface: candidate.surface });
+    if best.is_some() {
+        return best;
+    }
+    fallback_step(ground, surfaces, request, ray_budget - (ring_probes + 1), &mut gizmos)
+}
+
+// Lower is better. Stability is how far the other probe hits sit off this hit's plane, which is large on edges and steps.
+fn score(candidate: &Candidate, candidates: &[Option<Candidate>], search: &FootholdSearch, surfaces: &SurfaceQuery, desired_pos: Vec3) -> f32 {
+    let mut deviation = 0.;
+    let mut n = 0;
+    for other in candidates.iter().flatten() {
+        deviation += (other.hit.position - candidate.hit.position).dot(candidate.hit.normal).abs();
+        n += 1;
+    }
+    let instability = if n > 1 { deviation / (n - 1) as f32 / search.probe_radius } else { 1. };
+    let distance = candidate.hit.position.distance(desired_pos) / search.probe_radius;
+    search.slope_weight * candidate.slope / search.max_slope
+        + search.distance_weight * distance
+        + search.stability_weight * instability
+        + surfaces.rule(candidate.surface).cost
+}
+
+// Casts at most `rays` of its two rays, whatever the ring left of the budget.
+fn fallback_step(
+    ground: &mut impl GroundQuery,
+    surfaces: &SurfaceQuery,
+    request: &FootholdRequest,
+    rays: usize,
+    gizmos: &mut Option<&mut Gizmos>,
+) -> Option<Foothold> {
+    let FootholdRequest { search, transform, up, desired_pos, .. } = *request;
+    let transform = transform.compute_transform();
+    let mut custom = transform;
+    custom.translation = desired_pos;
+    custom.translation = custom.transform_point(Vec3::Y * 1.);
+    let origin = custom.translation - (custom.translation - transform.translation).normalize() * 0.75;
+    let origin2 = custom.translation + (custom.translation - transform.translation).normalize() * 1.5;
+    let ray = Ray3d::new(origin, (desired_pos - origin).normalize());
+    let ray2 = Ray3d::new(origin2, (desired_pos - origin2).normalize());
+    for (ray, max_distance) in [(ray, 1.5), (ray2, 4.)].into_iter().take(rays) {
+        let Some(hit) = probe(ground, surfaces, ray, max_distance, gizmos) else {continue;};
+        if let Some((surface, _)) = surfaces.accepts(&hit, up, search.max_slope) {
+            return Some(Foothold { position: hit.position, normal: hit.normal, surface });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extras_value_reads_the_key_not_a_value_that_looks_like_it() {
+        assert_eq!(extras_value(r#"{"surface":"ice"}"#, "surface").as_deref(), Some("ice"));
+        assert_eq!(extras_value(r#"{"name":"surface","surface":"ice"}"#, "surface").as_deref(), Some("ice"));
+        assert_eq!(extras_value(r#"{"note":"the \"surface\" is below","surface":"sticky"}"#, "surface").as_deref(), Some("sticky"));
+    }
+
+    #[test]
+    fn extras_value_unescapes_strings() {
+        assert_eq!(extras_value(r#"{"surface":"sli\"ppery"}"#, "surface").as_deref(), Some("sli\"ppery"));
+        assert_eq!(extras_value(r#"{"surface":"\u0069ce"}"#, "surface").as_deref(), Some("ice"));
+    }
+
+    #[test]
+    fn extras_value_skips_missing_keys_other_types_and_bad_json() {
+        assert_eq!(extras_value(r#"{"name":"surface"}"#, "surface"), None);
+        assert_eq!(extras_value(r#"{"surface":3}"#, "surface"), None);
+        assert_eq!(extras_value(r#"{"nested":{"surface":"ice"}}"#, "surface"), None);
+        assert_eq!(extras_value(r#"{"surface":"ice""#, "surface"), None);
+        assert_eq!(extras_value("", "surface"), None);
+    }
+}
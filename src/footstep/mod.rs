@@ -0,0 +1,111 @@
+use bevy::{audio::Volume, prelude::*};
+
+use crate::{foothold::SurfaceTag, leg::{FootEvent, IKLeg, LegCreature}, IKArm::IKArm};
+
+#[derive(Event, Clone, Debug)]
+pub struct FootLifted {
+    pub leg: Entity,
+    pub creature: Entity,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub surface: SurfaceTag,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct FootPlanted {
+    pub leg: Entity,
+    pub creature: Entity,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub surface: SurfaceTag,
+    // How fast the foot was moving on the frame it landed, in units per second.
+    pub impact_speed: f32,
+}
+
+// Legs only flag what happened this frame while they step in parallel, this turns the flags into events.
+pub(crate) fn emit_footstep_events(
+    leg_creature_query: Query<(Entity, &LegCreature)>,
+    mut leg_query: Query<(&IKArm, &mut IKLeg)>,
+    mut lifted_events: EventWriter<FootLifted>,
+    mut planted_events: EventWriter<FootPlanted>,
+) {
+    for (creature_entity, leg_creature) in leg_creature_query.iter() {
+        for (leg_entity, _) in &leg_creature.legs_info {
+            let Ok((arm, mut leg)) = leg_query.get_mut(*leg_entity) else {continue;};
+            match leg.take_foot_event() {
+                Some(FootEvent::Lifted) => {
+                    lifted_events.send(FootLifted {
+                        leg: *leg_entity,
+                        creature: creature_entity,
+                        position: arm.target,
+                        normal: leg.normal(),
+                        surface: leg.surface(),
+                    });
+                }
+                Some(FootEvent::Planted { impact_speed }) => {
+                    planted_events.send(FootPlanted {
+                        leg: *leg_entity,
+                        creature: creature_entity,
+                        position: arm.target,
+                        normal: leg.normal(),
+                        surface: leg.surface(),
+                        impact_speed,
+                    });
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+// Sounds for the optional FootstepAudioPlugin. Surfaces without their own sound use `default`.
+#[derive(Resource, Default)]
+pub struct FootstepSounds {
+    pub default: Option<Handle<AudioSource>>,
+    pub slippery: Option<Handle<AudioSource>>,
+    pub sticky: Option<Handle<AudioSource>>,
+    pub climbable: Option<Handle<AudioSource>>,
+    // Impact speed that plays at full volume, slower steps are quieter.
+    pub full_volume_speed: f32,
+    pub spatial: bool,
+}
+
+impl FootstepSounds {
+    pub fn get(&self, surface: SurfaceTag) -> Option<&Handle<AudioSource>> {
+        let sound = match surface {
+            SurfaceTag::Slippery => self.slippery.as_ref(),
+            SurfaceTag::Sticky => self.sticky.as_ref(),
+            SurfaceTag::Climbable => self.climbable.as_ref(),
+            SurfaceTag::Walkable | SurfaceTag::Unwalkable => None,
+        };
+        sound.or(self.default.as_ref())
+    }
+}
+
+pub struct FootstepAudioPlugin;
+
+impl Plugin for FootstepAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, play_footstep_sounds.run_if(resource_exists::<FootstepSounds>));
+    }
+}
+
+fn play_footstep_sounds(
+    mut commands: Commands,
+    mut planted_events: EventReader<FootPlanted>,
+    sounds: Res<FootstepSounds>,
+) {
+    for event in planted_events.read() {
+        let Some(source) = sounds.get(event.surface) else {continue;};
+        let volume = (event.impact_speed / sounds.full_volume_speed.max(f32::EPSILON)).clamp(0.1, 1.);
+        commands.spawn((
+            AudioBundle {
+                source: source.clone(),
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new(volume))
+                    .with_spatial(sounds.spatial),
+            },
+            TransformBundle::from_transform(Transform::from_translation(event.position)),
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+use std::marker::PhantomData;
+use bevy::{ecs::{entity::MapEntities, reflect::ReflectMapEntities, schedule::{InternedScheduleLabel, ScheduleLabel}, system::{StaticSystemParam, SystemParam}}, prelude::*};
+
+use crate::{dismember::GRAVITY, foothold::SurfaceQuery, ground::{DefaultGround, GroundQuery}, inspect::ReadOnly, leg::IKLeg, schedule::{configure_sets, LocomotionSet}, IKArm::{handle_ik, IKArm, IKArmTarget}};
+
+#[derive(Copy, Clone, PartialEq, Debug, Reflect)]
+enum GrabState {
+    Reaching { elapsed: f32 },
+    Holding { object: Entity },
+}
+
+// Insert together with an IKArmTarget pointing at the object to pick up. Legs leave the gait while grabbing
+// or holding, and rejoin it once the object is released.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component, MapEntities)]
+pub struct Grab {
+    // How close the end effector has to get before the object attaches.
+    pub tolerance: f32,
+    pub reach_speed: f32,
+    // The reach is abandoned if the object isn't caught by then.
+    pub give_up_after: f32,
+    // Where the object is held, relative to the arm's root.
+    pub hold_offset: Vec3,
+    #[reflect(@ReadOnly)]
+    state: GrabState,
+    #[reflect(@ReadOnly)]
+    release: Option<Vec3>,
+}
+
+impl Default for Grab {
+    fn default() -> Self {
+        Self { tolerance: 0.08, reach_speed: 2., give_up_after: 2., hold_offset: Vec3::new(0., 0.15, 0.2), state: GrabState::Reaching { elapsed: 0. }, release: None }
+    }
+}
+
+impl Grab {
+    pub fn held(&self) -> Option<Entity> {
+        match self.state {
+            GrabState::Holding { object } => Some(object),
+            GrabState::Reaching { .. } => None,
+        }
+    }
+
+    // Lets go of the held object, or stops reaching for it.
+    pub fn release(&mut self) {
+        self.release = Some(Vec3::ZERO);
+    }
+
+    pub fn throw(&mut self, velocity: Vec3) {
+        self.release = Some(velocity);
+    }
+}
+
+impl MapEntities for Grab {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let GrabState::Holding { object } = &mut self.state {
+            *object = entity_mapper.map_entity(*object);
+        }
+    }
+}
+
+// On an object while an arm holds it.
+#[derive(Component, Reflect)]
+#[reflect(Component, MapEntities)]
+pub struct Carried {
+    #[reflect(@ReadOnly)]
+    carrier: Entity,
+    #[reflect(@ReadOnly)]
+    offset: Vec3,
+}
+
+impl Carried {
+    pub fn carrier(&self) -> Entity {
+        self.carrier
+    }
+}
+
+impl MapEntities for Carried {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.carrier = entity_mapper.map_entity(self.carrier);
+    }
+}
+
+// A released object falling until it lands on the ground.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Thrown {
+    #[reflect(@ReadOnly)]
+    velocity: Vec3,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct Grabbed {
+    pub arm: Entity,
+    pub object: Entity,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct Released {
+    pub arm: Entity,
+    pub object: Entity,
+    pub velocity: Vec3,
+}
+
+// Thrown objects land through the same ground query backend as the legs.
+pub struct GrabPlugin<G = DefaultGround> {
+    schedule: InternedScheduleLabel,
+    marker: PhantomData<fn() -> G>,
+}
+
+impl GrabPlugin {
+    pub fn new() -> Self {
+        Self::with_ground()
+    }
+}
+
+impl Default for GrabPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> GrabPlugin<G> {
+    pub fn with_ground() -> Self {
+        Self { schedule: FixedUpdate.intern(), marker: PhantomData }
+    }
+
+    // Runs the systems in another schedule, e.g. FixedUpdate or PostUpdate. See LocomotionSet.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl<G: SystemParam + 'static> Plugin for GrabPlugin<G>
+where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    fn build(&self, app: &mut App) {
+        configure_sets(app, self.schedule);
+        app.register_type::<Grab>()
+        .register_type::<Carried>()
+        .register_type::<Thrown>()
+        .add_event::<Grabbed>()
+        .add_event::<Released>()
+        .add_systems(self.schedule, handle_grabs.in_set(LocomotionSet::FootPlacement))
+        // Carried objects sit on the arm tip, which is only known once the arm is solved.
+        .add_systems(self.schedule, (carry_objects, fly_thrown::<G>).chain().after(handle_ik).in_set(LocomotionSet::IkSolve));
+    }
+}
+
+type GrabbingArm = (Entity, &'static GlobalTransform, &'static mut IKArm, &'static mut Grab, &'static IKArmTarget, Option<&'static mut IKLeg>);
+
+fn handle_grabs(
+    mut commands: Commands,
+    mut grab_query: Query<GrabbingArm>,
+    object_query: Query<&GlobalTransform>,
+    mut grabbed_events: EventWriter<Grabbed>,
+    mut released_events: EventWriter<Released>,
+    time: Res<Time>,
+) {
+    for (arm_entity, transform, mut arm, mut grab, arm_target, leg) in grab_query.iter_mut() {
+        if let Some(mut leg) = leg {
+            leg.interrupt_step();
+        }
+        if let Some(velocity) = grab.release {
+            if let Some(object) = grab.held() {
+                if let Some(mut object_commands) = commands.get_entity(object) {
+                    object_commands.remove::<Carried>().insert(Thrown { velocity });
+                }
+                released_events.send(Released { arm: arm_entity, object, velocity });
+            }
+            commands.entity(arm_entity).remove::<(Grab, IKArmTarget)>();
+            continue;
+        }
+        match grab.state {
+            GrabState::Reaching { elapsed } => {
+                let Ok(object_transform) = object_query.get(arm_target.target) else {
+                    commands.entity(arm_entity).remove::<(Grab, IKArmTarget)>();
+                    continue;
+                };
+                let object_pos = object_transform.translation();
+                let to_object = object_pos - arm.target;
+                arm.target += to_object.clamp_length_max(grab.reach_speed * time.delta_seconds());
+                if arm.end_effector().distance(object_pos) <= grab.tolerance {
+                    grab.state = GrabState::Holding { object: arm_target.target };
+                    commands.entity(arm_target.target).remove::<Thrown>().insert(Carried { carrier: arm_entity, offset: object_pos - arm.end_effector() });
+                    grabbed_events.send(Grabbed { arm: arm_entity, object: arm_target.target });
+                } else if elapsed + time.delta_seconds() >= grab.give_up_after {
+                    commands.entity(arm_entity).remove::<(Grab, IKArmTarget)>();
+                } else {
+                    grab.state = GrabState::Reaching { elapsed: elapsed + time.delta_seconds() };
+                }
+            }
+            GrabState::Holding { object } => {
+                // Despawned out of its grip, there's nothing left to release.
+                if !object_query.contains(object) {
+                    commands.entity(arm_entity).remove::<(Grab, IKArmTarget)>();
+                    continue;
+                }
+                let hold = transform.transform_point(grab.hold_offset);
+                arm.target = arm.target.lerp(hold, 0.2);
+            }
+        }
+    }
+}
+
+fn carry_objects(
+    mut carried_query: Query<(&mut Transform, &Carried)>,
+    arm_query: Query<&IKArm>,
+) {
+    for (mut transform, carried) in carried_query.iter_mut() {
+        let Ok(arm) = arm_query.get(carried.carrier) else {continue;};
+        transform.translation = arm.end_effector() + carried.offset;
+    }
+}
+
+fn fly_thrown<G: SystemParam>(
+    mut commands: Commands,
+    mut thrown_query: Query<(Entity, &mut Transform, &mut Thrown)>,
+    parent_query: Query<&Parent>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+    time: Res<Time>,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let delta = time.delta_seconds();
+    for (object, mut transform, mut thrown) in thrown_query.iter_mut() {
+        thrown.velocity += Vec3::NEG_Y * GRAVITY * delta;
+        let motion = thrown.velocity * delta;
+        let Ok(direction) = Dir3::new(motion) else {continue;};
+        let ray = Ray3d { origin: transform.translation, direction };
+        let filter = |entity: Entity| {
+            entity != object && !parent_query.iter_ancestors(entity).any(|ancestor| ancestor == object) && !surfaces.is_creature_part(entity)
+        };
+        match ground.cast_ground_ray(ray, motion.length(), &filter) {
+            Some(hit) => {
+                transform.translation = hit.position;
+                commands.entity(object).remove::<Thrown>();
+            }
+            None => transform.translation += motion,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::{GroundHit, GroundQuery};
+
+pub type PhysicsGround = SpatialQuery<'static, 'static>;
+
+impl<'w, 's> GroundQuery for SpatialQuery<'w, 's> {
+    fn cast_ground_ray(&mut self, ray: Ray3d, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<GroundHit> {
+        let hit = self.cast_ray_predicate(ray.origin, ray.direction, max_distance, true, SpatialQueryFilter::default(), filter)?;
+        Some(GroundHit {
+            entity: Some(hit.entity),
+            position: ray.get_point(hit.time_of_impact),
+            normal: hit.normal,
+            distance: hit.time_of_impact,
+        })
+    }
+}
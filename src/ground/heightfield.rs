@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+use super::{GroundHit, GroundQuery};
+
+pub type HeightfieldGround = Res<'static, Heightfield>;
+
+// Terrain described by a height function over world XZ, for tests and projects without meshes to hit.
+#[derive(Resource)]
+pub struct Heightfield {
+    height: Box<dyn Fn(Vec2) -> f32 + Send + Sync>,
+    pub march_step: f32,
+}
+
+impl Heightfield {
+    pub fn new(height: impl Fn(Vec2) -> f32 + Send + Sync + 'static) -> Self {
+        Self { height: Box::new(height), march_step: 0.05 }
+    }
+
+    pub fn flat(height: f32) -> Self {
+        Self::new(move |_| height)
+    }
+
+    pub fn height_at(&self, point: Vec2) -> f32 {
+        (self.height)(point)
+    }
+
+    pub fn normal_at(&self, point: Vec2) -> Vec3 {
+        let e = self.march_step * 0.5;
+        let dx = self.height_at(point + Vec2::X * e) - self.height_at(point - Vec2::X * e);
+        let dz = self.height_at(point + Vec2::Y * e) - self.height_at(point - Vec2::Y * e);
+        Vec3::new(-dx, 2. * e, -dz).normalize()
+    }
+
+    fn above(&self, point: Vec3) -> bool {
+        point.y > self.height_at(point.xz())
+    }
+}
+
+impl<'w> GroundQuery for Res<'w, Heightfield> {
+    fn cast_ground_ray(&mut self, ray: Ray3d, max_distance: f32, _filter: &dyn Fn(Entity) -> bool) -> Option<GroundHit> {
+        if !self.above(ray.origin) {
+            return None;
+        }
+        // March until we go under the surface, then bisect the last step.
+        let mut near = 0.;
+        let mut far = 0.;
+        while far < max_distance {
+            far = (far + self.march_step).min(max_distance);
+            if !self.above(ray.get_point(far)) {
+                break;
+            }
+            near = far;
+        }
+        if self.above(ray.get_point(far)) {
+            return None;
+        }
+        for _ in 0..16 {
+            let middle = (near + far) / 2.;
+            if self.above(ray.get_point(middle)) {
+                near = middle;
+            } else {
+                far = middle;
+            }
+        }
+        let position = ray.get_point(far);
+        Some(GroundHit {
+            entity: None,
+            position,
+            normal: self.normal_at(position.xz()),
+            distance: far,
+        })
+    }
+}
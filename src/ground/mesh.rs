@@ -0,0 +1,26 @@
+use bevy::prelude::*;
+use bevy_mod_raycast::prelude::*;
+
+use super::{GroundHit, GroundQuery};
+
+pub type MeshGround = Raycast<'static, 'static>;
+
+impl<'w, 's> GroundQuery for Raycast<'w, 's> {
+    fn cast_ground_ray(&mut self, ray: Ray3d, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<GroundHit> {
+        let filter = |entity: Entity| filter(entity);
+        // Visibility is ignored so creatures off screen, or far away at a low LOD, still find the ground.
+        let raycast_settings = RaycastSettings::default()
+            .with_visibility(RaycastVisibility::Ignore)
+            .with_filter(&filter);
+        let (entity, hit_data) = self.cast_ray(ray, &raycast_settings).first()?;
+        if hit_data.distance() > max_distance {
+            return None;
+        }
+        Some(GroundHit {
+            entity: Some(*entity),
+            position: hit_data.position(),
+            normal: hit_data.normal(),
+            distance: hit_data.distance(),
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+
+#[cfg(feature = "avian")]
+mod avian;
+mod heightfield;
+#[cfg(feature = "raycast")]
+mod mesh;
+
+#[cfg(feature = "avian")]
+pub use avian::PhysicsGround;
+pub use heightfield::{Heightfield, HeightfieldGround};
+#[cfg(feature = "raycast")]
+pub use mesh::MeshGround;
+
+// The backend LegPlugin and PerceptionPlugin use when none is given: meshes if the `raycast` feature is on,
+// then the physics engine, and the Heightfield resource as the last resort.
+#[cfg(feature = "raycast")]
+pub type DefaultGround = MeshGround;
+#[cfg(all(not(feature = "raycast"), feature = "avian"))]
+pub type DefaultGround = PhysicsGround;
+#[cfg(all(not(feature = "raycast"), not(feature = "avian")))]
+pub type DefaultGround = HeightfieldGround;
+
+#[derive(Copy, Clone, Debug)]
+pub struct GroundHit {
+    // None when the backend has no entities, like an analytic heightfield.
+    pub entity: Option<Entity>,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+// Ray queries against the world, implemented by a system param item so backends can pull in whatever they need.
+// To plug in your own, implement this for `YourParam<'w, 's>` and add `LegPlugin::<YourParam<'static, 'static>>::with_ground()`.
+pub trait GroundQuery {
+    // First hit along the ray within max_distance whose entity passes the filter.
+    fn cast_ground_ray(&mut self, ray: Ray3d, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<GroundHit>;
+}
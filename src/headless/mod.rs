@@ -0,0 +1,77 @@
+use bevy::{gizmos::gizmos::GizmoStorage, input::InputPlugin, time::TimeUpdateStrategy, prelude::*};
+
+use crate::{leg::{IKLeg, LegCreature, LegSide}, procedural::{spawn_leg_rig, LegAssets, LegShape}, IKArm::IKArm};
+
+// Everything the locomotion plugins need to run without a window or renderer: MinimalPlugins, input, transforms, and a clock
+// that advances exactly one fixed timestep per `app.update()`, so N updates are N simulation ticks whatever the machine.
+// Add the locomotion plugins and a ground (e.g. the Heightfield resource) on top.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((MinimalPlugins, InputPlugin, TransformPlugin, HierarchyPlugin));
+        let timestep = app.world().resource::<Time<Fixed>>().timestep();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+        init_headless_gizmos(app);
+    }
+}
+
+// Just enough for `Gizmos` params to run without the renderer, the lines themselves are dropped.
+fn init_headless_gizmos(app: &mut App) {
+    app.init_resource::<GizmoConfigStore>()
+        .init_resource::<GizmoStorage<DefaultGizmoConfigGroup, ()>>();
+    app.world_mut().resource_mut::<GizmoConfigStore>().insert(GizmoConfig::default(), DefaultGizmoConfigGroup);
+}
+
+pub fn run_ticks(app: &mut App, ticks: u32) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+// Length of both bones of the legs spawned by `spawn_rig`, long enough to reach their feet at rest.
+pub const RIG_BONE_LENGTH: f32 = 0.35;
+
+// A four-legged creature laid out like the spider, with procedural legs instead of the glTF one. Nothing is
+// rendered, the meshes are left as default handles, the IK solver only looks at the joints. Returns the body.
+pub fn spawn_rig(world: &mut World, position: Vec3) -> Entity {
+    spawn_shaped_rig(world, position, &LegShape { lengths: vec![RIG_BONE_LENGTH; 2], ..default() })
+}
+
+// spawn_rig with legs of another shape.
+pub fn spawn_shaped_rig(world: &mut World, position: Vec3, shape: &LegShape) -> Entity {
+    let mut legs_info = Vec::new();
+    for (i, side_mult) in [1., -1.].into_iter().enumerate() {
+        for (j, front_or_back_mult) in [1., -1.].into_iter().enumerate() {
+            let leg_side = if i == j { LegSide::Left } else { LegSide::Right };
+            let offset = Vec3::new(0.15 * side_mult, -0.1, 0.1 * front_or_back_mult);
+            let leg = spawn_shaped_rig_leg(world, position + offset, shape, IKLeg::new(
+                Vec3::new(0.5 * side_mult, -0.1, 0.35 * front_or_back_mult),
+                0.1,
+                0.15,
+                0.3,
+                leg_side,
+                false,
+            ));
+            legs_info.push((leg, offset));
+        }
+    }
+    world.spawn((
+        SpatialBundle::from_transform(Transform::from_translation(position)),
+        LegCreature::new(LegSide::None, 0.2, legs_info),
+        Name::new("Rig"),
+    )).id()
+}
+
+// One of the legs of spawn_rig, loose. Add LegOf to hang it off a creature.
+pub fn spawn_rig_leg(world: &mut World, position: Vec3, leg: IKLeg) -> Entity {
+    spawn_shaped_rig_leg(world, position, &LegShape { lengths: vec![RIG_BONE_LENGTH; 2], ..default() }, leg)
+}
+
+fn spawn_shaped_rig_leg(world: &mut World, position: Vec3, shape: &LegShape, leg: IKLeg) -> Entity {
+    let assets = LegAssets { shape: shape.clone(), mesh: Handle::default(), inverse_bindposes: Handle::default(), material: Handle::default() };
+    let root = spawn_leg_rig(&mut world.commands(), &assets, Transform::from_translation(position));
+    world.flush();
+    world.entity_mut(root).insert((IKArm::new(position, Vec3::Y), leg));
+    root
+}
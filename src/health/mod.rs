@@ -0,0 +1,233 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{dismember::SeveredLeg, ground::{GroundHit, GroundQuery}, inspect::ReadOnly, leg::{IKLeg, LegCreature, LegOf}};
+
+// On a LegCreature body and optionally on each of its legs. Damage to a leg without Health goes to the body.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct Health {
+    pub max: f32,
+    pub current: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { max, current: max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0. { (self.current / self.max).clamp(0., 1.) } else { 0. }
+    }
+}
+
+// Scales damage dealt to this mesh or anything under it, e.g. a weak spot on the head.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct HitZone {
+    pub multiplier: f32,
+}
+
+impl Default for HitZone {
+    fn default() -> Self {
+        Self { multiplier: 1. }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Reflect)]
+pub enum DeathPose {
+    // Freezes where it stood.
+    Stand,
+    // Sinks onto its feet, like running out of legs.
+    Collapse,
+    // Falls to the ground with its legs curled up under it.
+    #[default] Crumple,
+    // Like Crumple, but the body falls as an avian3d rigid body. Needs the avian PhysicsPlugins.
+    #[cfg(feature = "ragdoll")]
+    Ragdoll,
+}
+
+// What happens to a creature when its body Health runs out, optional on a LegCreature.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct DeathResponse {
+    pub pose: DeathPose,
+    // Height of the body above the ground once it has crumpled.
+    pub rest_height: f32,
+    pub despawn_after: Option<f32>,
+}
+
+impl Default for DeathResponse {
+    fn default() -> Self {
+        Self { pose: DeathPose::default(), rest_height: 0.15, despawn_after: None }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Dead {
+    #[reflect(@ReadOnly)]
+    pub(crate) pose: DeathPose,
+    #[reflect(@ReadOnly)]
+    pub(crate) rest_height: f32,
+    #[reflect(@ReadOnly)]
+    despawn_in: Option<f32>,
+}
+
+// Send this to hurt whatever `entity` belongs to, typically the mesh a ray hit.
+#[derive(Event, Clone, Debug)]
+pub struct Damage {
+    pub entity: Entity,
+    pub amount: f32,
+    pub position: Vec3,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DamageTaken {
+    pub creature: Option<Entity>,
+    pub leg: Option<Entity>,
+    pub amount: f32,
+    pub position: Vec3,
+    pub remaining: f32,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct LegDestroyed {
+    pub creature: Option<Entity>,
+    pub leg: Entity,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct CreatureDied {
+    pub creature: Entity,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    pub creature: Option<Entity>,
+    pub leg: Option<Entity>,
+    pub multiplier: f32,
+}
+
+#[derive(SystemParam)]
+pub struct HitQuery<'w, 's> {
+    parent_query: Query<'w, 's, &'static Parent>,
+    leg_query: Query<'w, 's, Option<&'static LegOf>, With<IKLeg>>,
+    creature_query: Query<'w, 's, (), With<LegCreature>>,
+    zone_query: Query<'w, 's, &'static HitZone>,
+}
+
+impl<'w, 's> HitQuery<'w, 's> {
+    // Maps a hit mesh back to its leg and creature. Legs aren't children of their body, so the creature of a leg
+    // is found through LegOf.
+    pub fn resolve(&self, entity: Entity) -> Option<Hit> {
+        let mut multiplier = None;
+        let mut leg = None;
+        let mut creature = None;
+        for entity in std::iter::once(entity).chain(self.parent_query.iter_ancestors(entity)) {
+            if multiplier.is_none() {
+                multiplier = self.zone_query.get(entity).ok().map(|zone| zone.multiplier);
+            }
+            if leg.is_none() && self.leg_query.contains(entity) {
+                leg = Some(entity);
+            }
+            if self.creature_query.contains(entity) {
+                creature = Some(entity);
+                break;
+            }
+        }
+        if let (None, Some(leg)) = (creature, leg) {
+            creature = self.leg_query.get(leg).ok().flatten().map(|leg_of| leg_of.creature);
+        }
+        if creature.is_none() && leg.is_none() {
+            return None;
+        }
+        Some(Hit { creature, leg, multiplier: multiplier.unwrap_or(1.) })
+    }
+
+    // Shoots a ray through the ground query backend and returns the first creature part it hits.
+    pub fn cast(&self, ground: &mut impl GroundQuery, ray: Ray3d, max_distance: f32) -> Option<(Hit, GroundHit)> {
+        let hit = ground.cast_ground_ray(ray, max_distance, &|_| true)?;
+        Some((self.resolve(hit.entity?)?, hit))
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+        .register_type::<HitZone>()
+        .register_type::<DeathResponse>()
+        .register_type::<Dead>()
+        .add_event::<Damage>()
+        .add_event::<DamageTaken>()
+        .add_event::<LegDestroyed>()
+        .add_event::<CreatureDied>()
+        .add_systems(Update, (apply_damage, handle_death).chain());
+    }
+}
+
+#[derive(SystemParam)]
+struct DamageEvents<'w> {
+    taken: EventWriter<'w, DamageTaken>,
+    leg_destroyed: EventWriter<'w, LegDestroyed>,
+    died: EventWriter<'w, CreatureDied>,
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<Damage>,
+    hits: HitQuery,
+    mut health_query: Query<&mut Health>,
+    response_query: Query<&DeathResponse>,
+    mut events: DamageEvents,
+) {
+    for damage in damage_events.read() {
+        let Some(hit) = hits.resolve(damage.entity) else {continue;};
+        let amount = damage.amount * hit.multiplier;
+        let damaged_leg = hit.leg.filter(|leg| health_query.contains(*leg));
+        let Some(damaged) = damaged_leg.or(hit.creature) else {continue;};
+        let Ok(mut health) = health_query.get_mut(damaged) else {continue;};
+        if health.is_dead() {
+            continue;
+        }
+        health.current = (health.current - amount).max(0.);
+        events.taken.send(DamageTaken { creature: hit.creature, leg: damaged_leg, amount, position: damage.position, remaining: health.current });
+        if !health.is_dead() {
+            continue;
+        }
+        if let Some(leg) = damaged_leg {
+            commands.entity(leg).insert(SeveredLeg::new(Vec3::Y * 1.5));
+            events.leg_destroyed.send(LegDestroyed { creature: hit.creature, leg });
+        } else {
+            let response = response_query.get(damaged).cloned().unwrap_or_default();
+            commands.entity(damaged).insert(Dead { pose: response.pose, rest_height: response.rest_height, despawn_in: response.despawn_after });
+            events.died.send(CreatureDied { creature: damaged });
+        }
+    }
+}
+
+fn handle_death(
+    mut commands: Commands,
+    mut dead_query: Query<(Entity, &mut Dead, Option<&LegCreature>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut dead, leg_creature) in dead_query.iter_mut() {
+        let Some(despawn_in) = dead.despawn_in.as_mut() else {continue;};
+        *despawn_in -= time.delta_seconds();
+        if *despawn_in > 0. {
+            continue;
+        }
+        // Legs aren't children of the body, so they go separately.
+        for (leg_entity, _) in leg_creature.map_or(&[][..], |leg_creature| leg_creature.legs_info.as_slice()) {
+            if let Some(leg) = commands.get_entity(*leg_entity) {
+                leg.despawn_recursive();
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
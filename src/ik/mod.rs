@@ -0,0 +1,402 @@
+use std::f32::consts::{FRAC_PI_3, PI};
+use bevy::math::{Quat, Vec2, Vec3, Vec3Swizzles, EulerRot};
+
+// The inverse kinematics math on plain vectors, no ECS involved. The IKArm systems call into this, tools and tests
+// can use it directly. Bones point along their joint's local +Y, like the glTF leg.
+
+// Angle of a triangle between sides `a` and `b`, opposite the side `opposite`. Sides that can't close a triangle
+// give the nearest angle, 0 or PI, rather than NaN.
+pub fn triangle_angle(a: f32, b: f32, opposite: f32) -> f32 {
+    let cos = (a * a + b * b - opposite * opposite) / (2. * a * b);
+    if cos.is_nan() {
+        return 0.;
+    }
+    cos.clamp(-1., 1.).acos()
+}
+
+// Yaw of `dir` around Y measured from `-right`, and its pitch above the horizontal plane.
+pub fn yaw_and_pitch(right: Vec3, dir: Vec3) -> (f32, f32) {
+    let yaw = (-right).xz().angle_between(dir.xz());
+    let horizontal = Vec3::new(dir.x, 0., dir.z);
+    let mut pitch = dir.angle_between(horizontal);
+    if dir.y < 0. {
+        pitch = -pitch;
+    }
+    (yaw, pitch)
+}
+
+// Angle from `a` to `b`, positive when their cross product points along `normal`.
+pub fn signed_angle_between(a: Vec3, b: Vec3, normal: Vec3) -> f32 {
+    let v1 = a.normalize();
+    let v2 = b.normalize();
+
+    let dot_product = v1.dot(v2).clamp(-1.0, 1.0); // Clamp to avoid numerical errors
+    let unsigned_angle = dot_product.acos(); // This is the unsigned angle
+
+    let cross_product = v1.cross(v2);
+    let sign = cross_product.dot(normal.normalize());
+
+    if sign < 0.0 {
+        -unsigned_angle
+    } else {
+        unsigned_angle
+    }
+}
+
+// How far the knee has to turn around the root to target axis `dir` to point towards `pole`, measured from the
+// middle of that axis.
+pub fn pole_correction(middle: Vec3, knee: Vec3, pole: Vec3, dir: Vec3) -> f32 {
+    let knee_vec = (knee - middle).normalize();
+    signed_angle_between(knee_vec, pole, -dir)
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TwoBoneSolution {
+    // Local rotation of the root joint, in the frame of the arm.
+    pub root_rotation: Quat,
+    // Local rotation of the knee joint.
+    pub knee_rotation: Quat,
+    // Where the tip ends up, short of the target when it's out of reach.
+    pub end_effector: Vec3,
+    pub reached: bool,
+}
+
+// Points a two-bone arm rooted at `root`, facing away from `right`, at `target`. The knee bends around the arm's
+// local Z, use pole_correction to turn it afterwards.
+pub fn solve_two_bone(root: Vec3, right: Vec3, target: Vec3, upper: f32, lower: f32) -> TwoBoneSolution {
+    let dir = target - root;
+    let (yaw, pitch) = yaw_and_pitch(right, dir);
+    let reach = upper + lower;
+    let distance = dir.length().min(reach);
+    let root_angle = PI / 2. - triangle_angle(upper, distance, lower);
+    let knee_angle = PI - triangle_angle(upper, lower, distance);
+    TwoBoneSolution {
+        root_rotation: Quat::from_euler(EulerRot::XYZ, 0., -yaw, root_angle - pitch),
+        knee_rotation: Quat::from_rotation_z(knee_angle),
+        end_effector: root + dir.clamp_length_max(reach),
+        reached: dir.length() <= reach,
+    }
+}
+
+// Forward kinematics of a two-bone arm, where the tip is for the given joint rotations.
+pub fn two_bone_tip(root: Vec3, root_rotation: Quat, knee_rotation: Quat, upper: f32, lower: f32) -> Vec3 {
+    root + root_rotation * (Vec3::Y * upper) + root_rotation * knee_rotation * (Vec3::Y * lower)
+}
+
+// Turns `joint` around the line from `root` to `target` until it sits on the side `pole` points to. Leaves it alone
+// when the pole runs along the line or the joint is on it.
+pub fn project_onto_pole(root: Vec3, target: Vec3, joint: Vec3, pole: Vec3) -> Vec3 {
+    let Some(axis) = (target - root).try_normalize() else {return joint;};
+    let center = root + axis * (joint - root).dot(axis);
+    let radius = (joint - center).length();
+    let Some(side) = pole.reject_from_normalized(axis).try_normalize() else {return joint;};
+    center + side * radius
+}
+
+// FABRIK over any number of joints, from the root at `joints[0]` to the tip, with bone `i` `lengths[i]` long between
+// joints `i` and `i + 1`. The root stays put. With a pole, inner joints bend towards it. Returns whether the tip
+// reached the target.
+pub fn solve_chain(joints: &mut [Vec3], lengths: &[f32], target: Vec3, pole: Option<Vec3>, tolerance: f32, max_iterations: usize) -> bool {
+    assert_eq!(lengths.len() + 1, joints.len(), "a chain needs a length for each pair of joints");
+    if joints.len() < 2 {
+        return false;
+    }
+    let root = joints[0];
+    if root.distance(target) >= lengths.iter().sum::<f32>() {
+        // Out of reach, stretch straight towards it.
+        let dir = (target - root).normalize_or_zero();
+        for i in 1..joints.len() {
+            joints[i] = joints[i - 1] + dir * lengths[i - 1];
+        }
+        return joints.last().unwrap().distance(target) <= tolerance;
+    }
+    if let Some(pole) = pole {
+        for i in 1..joints.len() - 1 {
+            joints[i] = project_onto_pole(root, target, joints[i], pole);
+        }
+    }
+    let last = joints.len() - 1;
+    for _ in 0..max_iterations {
+        if joints[last].distance(target) <= tolerance {
+            return true;
+        }
+        joints[last] = target;
+        for i in (0..last).rev() {
+            joints[i] = joints[i + 1] + (joints[i] - joints[i + 1]).normalize_or_zero() * lengths[i];
+        }
+        joints[0] = root;
+        for i in 1..joints.len() {
+            joints[i] = joints[i - 1] + (joints[i] - joints[i - 1]).normalize_or_zero() * lengths[i - 1];
+        }
+    }
+    joints[last].distance(target) <= tolerance
+}
+
+// solve_chain for bones `lengths` long rooted at `root`, bent towards `pole`. It starts from the same arc bulging
+// towards the pole every time rather than from the last pose, so the result only depends on the target. Puts every
+// joint from the root to the tip in `joints`, reusing its allocation, and returns whether the tip reached the target.
+pub fn solve_chain_from_arc(root: Vec3, target: Vec3, lengths: &[f32], pole: Vec3, tolerance: f32, max_iterations: usize, joints: &mut Vec<Vec3>) -> bool {
+    let dir = (target - root).try_normalize().unwrap_or(-pole);
+    let side = pole.reject_from(dir).try_normalize().unwrap_or_else(|| dir.any_orthonormal_vector());
+    joints.clear();
+    joints.push(root);
+    for (i, length) in lengths.iter().enumerate() {
+        // From leaning towards the pole at the root to leaning away from it at the tip.
+        let angle = FRAC_PI_3 * (1. - 2. * (i as f32 + 0.5) / lengths.len() as f32);
+        let bone = dir * angle.cos() + side * angle.sin();
+        joints.push(*joints.last().unwrap() + bone * *length);
+    }
+    solve_chain(joints, lengths, target, Some(pole), tolerance, max_iterations)
+}
+
+// Averages the planes through every combination of three points, oriented towards `up`. Returns the normal and a
+// point on the plane, or None without a single proper triangle among the points.
+pub fn fit_plane(points: &[Vec3], up: Vec3) -> Option<(Vec3, Vec3)> {
+    fit_plane_by(points, up, |point| *point)
+}
+
+// fit_plane over anything with a position, walking the combinations by index so nothing is allocated.
+pub fn fit_plane_by<T>(items: &[T], up: Vec3, position: impl Fn(&T) -> Vec3) -> Option<(Vec3, Vec3)> {
+    let mut normal_total = Vec3::ZERO;
+    let mut pos_total = Vec3::ZERO;
+    let mut n = 0;
+    for i in 0..items.len() {
+        for j in (i + 1)..items.len() {
+            for k in (j + 1)..items.len() {
+                let (v1, v2, v3) = (position(&items[i]), position(&items[j]), position(&items[k]));
+                if v1.is_nan() || v2.is_nan() || v3.is_nan() {
+                    return None;
+                }
+                // Points in a line don't make a plane.
+                let Some(normal) = (v2 - v1).cross(v3 - v1).try_normalize() else {continue;};
+                // Whichever way round the points go, the planes all face the same side.
+                normal_total += if normal.dot(up) < 0. { -normal } else { normal };
+                pos_total += (v1 + v2 + v3) / 3.;
+                n += 1;
+            }
+        }
+    }
+    if n == 0 {
+        return None;
+    }
+    let normal = normal_total.try_normalize()?;
+    Some((normal, pos_total / n as f32))
+}
+
+// Whether `point` stands over the convex hull of `feet` seen down `up`, or no further than `margin` outside of it.
+// Fewer than three feet make a segment or a point, which only the margin can stand over.
+pub fn support_polygon_contains(feet: &[Vec3], point: Vec3, up: Vec3, margin: f32) -> bool {
+    let (x, y) = up.any_orthonormal_pair();
+    let flatten = |point: Vec3| Vec2::new(point.dot(x), point.dot(y));
+    let hull = convex_hull(feet.iter().map(|foot| flatten(*foot)).collect());
+    let point = flatten(point);
+    let edges = || (0..hull.len()).map(|i| (hull[i], hull[(i + 1) % hull.len()]));
+    if hull.len() >= 3 && edges().all(|(a, b)| (b - a).perp_dot(point - a) >= 0.) {
+        return true;
+    }
+    edges().any(|(a, b)| {
+        let t = ((point - a).dot(b - a) / (b - a).length_squared().max(f32::EPSILON)).clamp(0., 1.);
+        point.distance(a + (b - a) * t) <= margin
+    })
+}
+
+// Andrew's monotone chain, counter-clockwise without collinear points.
+fn convex_hull(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<Vec2> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2 && (hull[hull.len() - 1] - hull[hull.len() - 2]).perp_dot(point - hull[hull.len() - 2]) <= 0. {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point of each pass is the first of the next.
+        hull.pop();
+    }
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn random_vec(rng: &mut StdRng, extent: f32) -> Vec3 {
+        Vec3::new(rng.gen_range(-extent..extent), rng.gen_range(-extent..extent), rng.gen_range(-extent..extent))
+    }
+
+    #[test]
+    fn triangle_angle_follows_the_law_of_cosines() {
+        assert!((triangle_angle(3., 4., 5.) - PI / 2.).abs() < EPSILON);
+        assert!((triangle_angle(1., 1., 1.) - PI / 3.).abs() < EPSILON);
+    }
+
+    #[test]
+    fn triangle_angle_clamps_impossible_triangles() {
+        assert_eq!(triangle_angle(1., 1., 3.), PI);
+        assert_eq!(triangle_angle(1., 3., 1.), 0.);
+        assert_eq!(triangle_angle(0., 1., 1.), 0.);
+    }
+
+    #[test]
+    fn signed_angle_sign_follows_the_normal() {
+        let angle = signed_angle_between(Vec3::X, Vec3::Z, Vec3::NEG_Y);
+        assert!((angle - PI / 2.).abs() < EPSILON);
+        assert!((signed_angle_between(Vec3::X, Vec3::Z, Vec3::Y) + PI / 2.).abs() < EPSILON);
+        assert!(signed_angle_between(Vec3::X, Vec3::X * 2., Vec3::Y).abs() < EPSILON);
+    }
+
+    #[test]
+    fn yaw_and_pitch_of_level_and_steep_directions() {
+        let (yaw, pitch) = yaw_and_pitch(Vec3::NEG_X, Vec3::X);
+        assert!(yaw.abs() < EPSILON && pitch.abs() < EPSILON);
+        let (_, pitch) = yaw_and_pitch(Vec3::NEG_X, Vec3::new(1., -1., 0.));
+        assert!((pitch + PI / 4.).abs() < EPSILON);
+    }
+
+    #[test]
+    fn two_bone_reaches_targets_in_range() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            let root = random_vec(&mut rng, 5.);
+            let (upper, lower): (f32, f32) = (rng.gen_range(0.1..1.), rng.gen_range(0.1..1.));
+            let min_reach = (upper - lower).abs() + 0.01;
+            let distance = rng.gen_range(min_reach..upper + lower - 0.01);
+            let target = root + random_vec(&mut rng, 1.).try_normalize().unwrap_or(Vec3::X) * distance;
+            let solution = solve_two_bone(root, Vec3::X, target, upper, lower);
+            let tip = two_bone_tip(root, solution.root_rotation, solution.knee_rotation, upper, lower);
+            assert!(solution.reached);
+            assert!(tip.distance(target) < 1e-3, "tip {tip} for target {target}, bones {upper} {lower}");
+            assert!(solution.end_effector.distance(target) < EPSILON);
+        }
+    }
+
+    #[test]
+    fn two_bone_stretches_towards_targets_out_of_range() {
+        let target = Vec3::new(3., -1., 2.);
+        let solution = solve_two_bone(Vec3::ZERO, Vec3::X, target, 0.5, 0.5);
+        let tip = two_bone_tip(Vec3::ZERO, solution.root_rotation, solution.knee_rotation, 0.5, 0.5);
+        assert!(!solution.reached);
+        assert!((tip.length() - 1.).abs() < 1e-3);
+        assert!(tip.normalize().distance(target.normalize()) < 1e-3);
+        assert!(solution.end_effector.distance(tip) < 1e-3);
+    }
+
+    #[test]
+    fn pole_projection_keeps_the_joint_distance_and_faces_the_pole() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..1000 {
+            let (root, target, joint, pole) = (random_vec(&mut rng, 2.), random_vec(&mut rng, 2.), random_vec(&mut rng, 2.), random_vec(&mut rng, 1.));
+            let projected = project_onto_pole(root, target, joint, pole);
+            assert!((projected.distance(root) - joint.distance(root)).abs() < 1e-3);
+            assert!((projected.distance(target) - joint.distance(target)).abs() < 1e-3);
+            let axis = (target - root).normalize();
+            let center = root + axis * (projected - root).dot(axis);
+            assert!((projected - center).dot(pole) >= -1e-3);
+        }
+    }
+
+    #[test]
+    fn pole_projection_ignores_poles_along_the_axis() {
+        let joint = Vec3::new(0.5, 0.5, 0.);
+        assert_eq!(project_onto_pole(Vec3::ZERO, Vec3::X, joint, Vec3::X), joint);
+    }
+
+    #[test]
+    fn chain_reaches_targets_in_range_keeping_bone_lengths() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            let mut joints = [Vec3::ZERO, Vec3::Y * 0.4, Vec3::Y * 0.7, Vec3::Y * 0.9];
+            let target = random_vec(&mut rng, 1.).clamp_length_max(0.85);
+            assert!(solve_chain(&mut joints, &[0.4, 0.3, 0.2], target, Some(Vec3::Z), 1e-3, 50), "missed {target}, got {:?}", joints);
+            assert_eq!(joints[0], Vec3::ZERO);
+            for (pair, length) in joints.windows(2).zip([0.4, 0.3, 0.2]) {
+                assert!((pair[0].distance(pair[1]) - length).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn chain_stretches_towards_targets_out_of_range() {
+        let mut joints = [Vec3::ZERO, Vec3::Y, Vec3::Y * 2.];
+        assert!(!solve_chain(&mut joints, &[1., 1.], Vec3::X * 5., None, 1e-3, 10));
+        assert!(joints[1].distance(Vec3::X).abs() < EPSILON);
+        assert!(joints[2].distance(Vec3::X * 2.).abs() < EPSILON);
+    }
+
+    #[test]
+    fn arc_seeded_chain_bends_towards_the_pole() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let lengths = [0.3, 0.25, 0.2];
+        let mut joints = Vec::new();
+        for _ in 0..200 {
+            let target = random_vec(&mut rng, 1.).clamp_length_max(0.7);
+            let pole = random_vec(&mut rng, 1.).normalize();
+            assert!(solve_chain_from_arc(Vec3::ZERO, target, &lengths, pole, 1e-4, 30, &mut joints));
+            assert_eq!(joints.len(), 4);
+            assert!(joints[3].distance(target) < 1e-3, "missed {target}, got {joints:?}");
+            for (pair, length) in joints.windows(2).zip(lengths) {
+                assert!((pair[0].distance(pair[1]) - length).abs() < 1e-3);
+            }
+            // Bent, not folded back on itself, on the side of the pole.
+            let bend = (joints[1] + joints[2]) / 2. - target / 2.;
+            if pole.reject_from(target).length() > 0.1 && bend.length() > 1e-3 {
+                assert!(bend.dot(pole.reject_from(target)) > 0., "bent away from {pole}: {joints:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn plane_through_a_tilted_square() {
+        let normal = Vec3::new(0., 1., -0.3).normalize();
+        let (tangent, bitangent) = normal.any_orthonormal_pair();
+        let center = Vec3::new(1., 2., 3.);
+        let corners = [tangent + bitangent, tangent - bitangent, -tangent - bitangent, -tangent + bitangent].map(|corner| center + corner);
+        let (fitted_normal, point) = fit_plane(&corners, Vec3::Y).unwrap();
+        assert!(fitted_normal.distance(normal) < EPSILON);
+        assert!((point - center).dot(normal).abs() < EPSILON);
+    }
+
+    #[test]
+    fn plane_faces_up_whatever_the_point_order() {
+        let points = [Vec3::new(1., 0., 1.), Vec3::new(1., 0., -1.), Vec3::new(-1., 0., 1.), Vec3::new(-1., 0., -1.)];
+        let (normal, _) = fit_plane(&points, Vec3::Y).unwrap();
+        assert!(normal.distance(Vec3::Y) < EPSILON);
+    }
+
+    #[test]
+    fn plane_needs_a_proper_triangle() {
+        assert!(fit_plane(&[Vec3::ZERO, Vec3::X], Vec3::Y).is_none());
+        assert!(fit_plane(&[Vec3::ZERO, Vec3::X, Vec3::X * 2.], Vec3::Y).is_none());
+        assert!(fit_plane(&[Vec3::ZERO, Vec3::X, Vec3::NAN], Vec3::Y).is_none());
+        // A duplicate foot doesn't spoil the others.
+        assert!(fit_plane(&[Vec3::ZERO, Vec3::ZERO, Vec3::X, Vec3::Z], Vec3::Y).is_some());
+    }
+
+    #[test]
+    fn support_polygon_holds_points_over_the_feet() {
+        let square = [Vec3::new(1., 0., 1.), Vec3::new(1., 0., -1.), Vec3::new(-1., 0., 1.), Vec3::new(-1., 0., -1.), Vec3::ZERO];
+        assert!(support_polygon_contains(&square, Vec3::new(0.5, 3., -0.9), Vec3::Y, 0.));
+        assert!(!support_polygon_contains(&square, Vec3::new(1.2, 0., 0.), Vec3::Y, 0.1));
+        assert!(support_polygon_contains(&square, Vec3::new(1.05, 0., 0.), Vec3::Y, 0.1));
+        // All on one side.
+        let lopsided = [Vec3::new(1., 0., 1.), Vec3::new(1., 0., -1.), Vec3::new(2., 0., 1.), Vec3::new(2., 0., -1.)];
+        assert!(!support_polygon_contains(&lopsided, Vec3::ZERO, Vec3::Y, 0.1));
+    }
+
+    #[test]
+    fn support_polygon_of_a_line_only_holds_what_the_margin_covers() {
+        let line = [Vec3::new(-1., 0., -1.), Vec3::ZERO, Vec3::new(1., 0., 1.)];
+        assert!(support_polygon_contains(&line, Vec3::new(0.5, 1., 0.5), Vec3::Y, 0.01));
+        assert!(!support_polygon_contains(&line, Vec3::new(0.5, 1., 0.), Vec3::Y, 0.01));
+        assert!(!support_polygon_contains(&[], Vec3::ZERO, Vec3::Y, 1.));
+    }
+}
@@ -0,0 +1,15 @@
+// Reflection helpers for editors and inspectors. Every locomotion component is registered with the type registry, so
+// it can be edited live, saved in scenes and shown by inspectors like bevy-inspector-egui.
+use bevy::{prelude::*, reflect::{TypeInfo, Typed}};
+
+// Custom reflect attribute on fields that hold simulation state rather than parameters. They're reflected so
+// inspectors can show them, but get overwritten every tick, inspectors should show them greyed out, e.g.
+// `field.has_attribute::<ReadOnly>()` on the StructInfo field.
+#[derive(Reflect, Copy, Clone, Debug)]
+pub struct ReadOnly;
+
+// Whether a field of a registered type is simulation state, see ReadOnly.
+pub fn is_read_only<T: Typed>(field: &str) -> bool {
+    let TypeInfo::Struct(info) = T::type_info() else {return false;};
+    info.field(field).is_some_and(|field| field.has_attribute::<ReadOnly>())
+}
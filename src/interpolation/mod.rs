@@ -0,0 +1,107 @@
+use bevy::{prelude::*, transform::systems::{propagate_transforms, sync_simple_transforms}};
+
+use crate::{leg::LegCreature, IKArm::{ArmRig, IKArm}};
+
+// The last two fixed-tick transforms of a simulated entity. Between ticks its Transform is blended between them
+// for rendering, and put back to the latest tick before the next one runs, so the simulation never sees the blend.
+// Added to creatures, arms and the joints the IK poses automatically, add it to anything else moved in the fixed
+// schedule. Transforms written from outside the fixed schedule, e.g. a teleport, are taken over as they are.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct SimTransform {
+    previous: Transform,
+    current: Transform,
+    // What was last put in the Transform from here, anything else found there was written by someone else.
+    shown: Transform,
+}
+
+impl SimTransform {
+    pub fn new(transform: Transform) -> Self {
+        Self { previous: transform, current: transform, shown: transform }
+    }
+
+    // Whether the Transform was written since it was last blended, restored or recorded.
+    fn moved_outside(&self, transform: &Transform) -> bool {
+        *transform != self.shown
+    }
+
+    // The transform as of the last fixed tick.
+    pub fn current(&self) -> Transform {
+        self.current
+    }
+}
+
+// Visual interpolation for locomotion running in FixedUpdate, added by LocomotionPlugin when it runs there.
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        // Global transforms are propagated again from the restored state, so the tick reads the same values
+        // however many frames were rendered in between.
+        app.register_type::<SimTransform>()
+        .add_systems(FixedFirst, (restore_sim_transforms, sync_simple_transforms, propagate_transforms).chain())
+        .add_systems(FixedLast, (track_sim_transforms, record_sim_transforms).chain())
+        .add_systems(Update, interpolate_sim_transforms);
+    }
+}
+
+// Bodies and arms that haven't been given a simulated transform yet.
+type Untracked = (Or<(With<LegCreature>, With<IKArm>)>, Without<SimTransform>);
+
+fn track_sim_transforms(
+    mut commands: Commands,
+    new_query: Query<(Entity, &Transform), Untracked>,
+    // The IK only turns the joints, left alone they'd snap from tick to tick under a smoothly moving arm.
+    rig_query: Query<&ArmRig, Added<ArmRig>>,
+    joint_query: Query<&Transform, Without<SimTransform>>,
+) {
+    for (entity, transform) in new_query.iter() {
+        commands.entity(entity).insert(SimTransform::new(*transform));
+    }
+    for joint in rig_query.iter().flat_map(|rig| rig.joints.iter().copied()) {
+        let Ok(transform) = joint_query.get(joint) else {continue;};
+        commands.entity(joint).insert(SimTransform::new(*transform));
+    }
+}
+
+fn restore_sim_transforms(
+    mut sim_query: Query<(&mut Transform, &mut SimTransform)>,
+) {
+    for (mut transform, mut sim) in sim_query.iter_mut() {
+        if sim.moved_outside(&transform) {
+            // Moved to, not towards, so there's nothing to blend from.
+            *sim = SimTransform::new(*transform);
+            continue;
+        }
+        // Avoids flagging untouched transforms as changed.
+        transform.set_if_neq(sim.current);
+        sim.shown = sim.current;
+    }
+}
+
+fn record_sim_transforms(
+    mut sim_query: Query<(&Transform, &mut SimTransform)>,
+) {
+    for (transform, mut sim) in sim_query.iter_mut() {
+        sim.previous = sim.current;
+        sim.current = *transform;
+        sim.shown = *transform;
+    }
+}
+
+fn interpolate_sim_transforms(
+    mut sim_query: Query<(&mut Transform, &mut SimTransform)>,
+    time: Res<Time<Fixed>>,
+) {
+    let t = time.overstep_fraction();
+    for (mut transform, mut sim) in sim_query.iter_mut() {
+        if sim.moved_outside(&transform) {
+            *sim = SimTransform::new(*transform);
+            continue;
+        }
+        transform.translation = sim.previous.translation.lerp(sim.current.translation, t);
+        transform.rotation = sim.previous.rotation.slerp(sim.current.rotation, t);
+        transform.scale = sim.previous.scale.lerp(sim.current.scale, t);
+        sim.shown = *transform;
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::{ecs::system::{StaticSystemParam, SystemParam}, prelude::*};
+
+use crate::{dismember::{DisabledLeg, LegLossResponse, GRAVITY}, foothold::{FootholdSearch, SurfaceQuery}, ground::GroundQuery, inspect::ReadOnly, leg::{IKLeg, LegCreature}, lod::LocomotionLod, ragdoll::Crumple, IKArm::IKArm};
+
+// How far down an airborne creature looks for where it will land.
+const LANDING_PROBE_DISTANCE: f32 = 50.;
+
+// Insert on a LegCreature to make it jump. It crouches first, then launches with `velocity`.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct Jump {
+    pub velocity: Vec3,
+    pub crouch_time: f32,
+    // Fraction of the body height the creature crouches by before launching.
+    pub crouch_depth: f32,
+    #[reflect(@ReadOnly)]
+    elapsed: f32,
+}
+
+impl Jump {
+    pub fn new(velocity: Vec3) -> Self {
+        Self { velocity, crouch_time: 0.2, crouch_depth: 0.5, elapsed: 0. }
+    }
+}
+
+// On a creature that has left the ground, either by jumping or by losing its footing.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Airborne {
+    #[reflect(@ReadOnly)]
+    velocity: Vec3,
+}
+
+impl Airborne {
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+}
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct AirborneLeg;
+
+// How a creature absorbs landings, optional on a LegCreature.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub struct Landing {
+    // Fraction of the body height it sinks by per unit of impact speed.
+    pub absorb_per_speed: f32,
+    pub max_absorb: f32,
+    // How fast it stands back up, in body heights per second.
+    pub recovery_speed: f32,
+}
+
+impl Default for Landing {
+    fn default() -> Self {
+        Self { absorb_per_speed: 0.08, max_absorb: 0.6, recovery_speed: 2. }
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct Jumped {
+    pub creature: Entity,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct StartedFalling {
+    pub creature: Entity,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct Landed {
+    pub creature: Entity,
+    pub impact_speed: f32,
+}
+
+fn take_off(commands: &mut Commands, creature_entity: Entity, leg_creature: &LegCreature, velocity: Vec3) {
+    commands.entity(creature_entity).insert(Airborne { velocity });
+    for (leg_entity, _) in &leg_creature.legs_info {
+        let Some(mut leg) = commands.get_entity(*leg_entity) else {continue;};
+        leg.insert(AirborneLeg);
+    }
+}
+
+type JumpingCreature = (Entity, &'static mut LegCreature, Option<&'static mut Jump>, Option<&'static Landing>);
+type OnItsFeet = (Without<Airborne>, Without<Crumple>);
+
+pub(crate) fn handle_jumps(
+    mut commands: Commands,
+    mut creature_query: Query<JumpingCreature, OnItsFeet>,
+    mut jumped_events: EventWriter<Jumped>,
+    time: Res<Time>,
+) {
+    let default_landing = Landing::default();
+    let delta = time.delta_seconds();
+    for (creature_entity, mut leg_creature, jump, landing) in creature_query.iter_mut() {
+        let Some(mut jump) = jump else {
+            if leg_creature.crouch > 0. {
+                let recovery = landing.unwrap_or(&default_landing).recovery_speed * delta;
+                leg_creature.crouch = (leg_creature.crouch - recovery).max(0.);
+            }
+            continue;
+        };
+        jump.elapsed += delta;
+        let t = (jump.elapsed / jump.crouch_time.max(f32::EPSILON)).min(1.);
+        leg_creature.crouch = leg_creature.crouch.max(jump.crouch_depth * t * t * (3. - 2. * t));
+        if jump.elapsed < jump.crouch_time {
+            continue;
+        }
+        commands.entity(creature_entity).remove::<Jump>();
+        take_off(&mut commands, creature_entity, &leg_creature, jump.velocity);
+        jumped_events.send(Jumped { creature: creature_entity });
+    }
+}
+
+type FallingCreature = (Entity, &'static GlobalTransform, &'static LegCreature, Option<&'static FootholdSearch>, Option<&'static LegLossResponse>, Option<&'static LocomotionLod>);
+type MayFall = (Without<Airborne>, Without<Jump>, Without<Crumple>);
+
+// Nothing under the body within reach of its feet means it walked off something, unless enough feet still stand on
+// either side of whatever is under it, like a gap narrower than its stride.
+pub(crate) fn detect_falls<G: SystemParam>(
+    mut commands: Commands,
+    creature_query: Query<FallingCreature, MayFall>,
+    leg_query: Query<&IKArm, Without<DisabledLeg>>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+    mut falling_events: EventWriter<StartedFalling>,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let default_search = FootholdSearch::default();
+    let default_response = LegLossResponse::default();
+    let filter = |entity: Entity| !surfaces.is_creature_part(entity);
+    for (creature_entity, transform, leg_creature, search, response, lod) in creature_query.iter() {
+        if lod.is_some_and(|lod| !lod.should_update()) {
+            continue;
+        }
+        let Ok(direction) = Dir3::new(-leg_creature.up()) else {continue;};
+        let reach = leg_creature.target_height + search.unwrap_or(&default_search).max_drop;
+        let ray = Ray3d { origin: transform.translation(), direction };
+        if ground.cast_ground_ray(ray, reach, &filter).is_some() {
+            continue;
+        }
+        let mut supported_feet = 0;
+        for (leg_entity, _) in &leg_creature.legs_info {
+            let Ok(arm) = leg_query.get(*leg_entity) else {continue;};
+            let ray = Ray3d { origin: arm.target + leg_creature.up() * leg_creature.target_height, direction };
+            if ground.cast_ground_ray(ray, reach, &filter).is_some() {
+                supported_feet += 1;
+            }
+        }
+        if supported_feet >= response.unwrap_or(&default_response).min_support_legs {
+            continue;
+        }
+        take_off(&mut commands, creature_entity, leg_creature, Vec3::ZERO);
+        falling_events.send(StartedFalling { creature: creature_entity });
+    }
+}
+
+pub(crate) fn fly_creatures<G: SystemParam>(
+    mut commands: Commands,
+    mut creature_query: Query<(Entity, &mut Transform, &mut LegCreature, &mut Airborne, Option<&Landing>)>,
+    mut leg_query: Query<(&mut IKArm, &mut IKLeg), With<AirborneLeg>>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+    mut landed_events: EventWriter<Landed>,
+    time: Res<Time>,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let default_landing = Landing::default();
+    let delta = time.delta_seconds();
+    let filter = |entity: Entity| !surfaces.is_creature_part(entity);
+    for (creature_entity, mut transform, mut leg_creature, mut airborne, landing) in creature_query.iter_mut() {
+        airborne.velocity += Vec3::NEG_Y * GRAVITY * delta;
+        let motion = airborne.velocity * delta;
+        let height = leg_creature.target_height * leg_creature.height_scale;
+        let down = Ray3d { origin: transform.translation, direction: Dir3::NEG_Y };
+        let below = ground.cast_ground_ray(down, LANDING_PROBE_DISTANCE, &filter);
+
+        // Lands once it's coming down and this frame's fall would take it below standing height.
+        if let Some(hit) = below.filter(|hit| airborne.velocity.y <= 0. && hit.distance <= height - motion.y) {
+            let impact_speed = -airborne.velocity.y;
+            let landing = landing.unwrap_or(&default_landing);
+            transform.translation = hit.position + Vec3::Y * height;
+            leg_creature.crouch = (impact_speed * landing.absorb_per_speed).min(landing.max_absorb);
+            for (leg_entity, leg_offset) in &leg_creature.legs_info {
+                let Ok((mut arm, mut leg)) = leg_query.get_mut(*leg_entity) else {continue;};
+                leg.interrupt_step();
+                let foot = transform.translation + *leg_offset + leg.step_offset;
+                let ray = Ray3d { origin: foot + Vec3::Y * height, direction: Dir3::NEG_Y };
+                if let Some(foot_hit) = ground.cast_ground_ray(ray, height * 4., &filter) {
+                    arm.target = foot_hit.position;
+                }
+                commands.entity(*leg_entity).remove::<AirborneLeg>();
+            }
+            commands.entity(creature_entity).remove::<Airborne>();
+            landed_events.send(Landed { creature: creature_entity, impact_speed });
+            continue;
+        }
+        transform.translation += motion;
+
+        // Legs tuck in on the way up and reach for where the body is going to land on the way down.
+        let landing_center = below.filter(|_| airborne.velocity.y <= 0.).map(|hit| {
+            let drop = (transform.translation.y - hit.position.y - height).max(0.);
+            let fall_speed = -airborne.velocity.y;
+            let time_to_land = (-fall_speed + (fall_speed * fall_speed + 2. * GRAVITY * drop).sqrt()) / GRAVITY;
+            let horizontal = Vec3::new(airborne.velocity.x, 0., airborne.velocity.z);
+            Vec3::new(transform.translation.x, hit.position.y, transform.translation.z) + horizontal * time_to_land
+        });
+        for (leg_entity, leg_offset) in &leg_creature.legs_info {
+            let Ok((mut arm, leg)) = leg_query.get_mut(*leg_entity) else {continue;};
+            let pose = match landing_center {
+                Some(center) => center + (*leg_offset + leg.step_offset) * Vec3::new(1., 0., 1.),
+                None => transform.translation + *leg_offset * 0.6 - Vec3::Y * height * 0.3,
+            };
+            arm.target = arm.target.lerp(pose, 0.2);
+        }
+    }
+}
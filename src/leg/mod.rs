@@ -1,17 +1,23 @@
-use std::f32::consts::PI;
-use bevy::{color::palettes::css::BLACK, math::{NormedVectorSpace, VectorSpace}, prelude::*, reflect::Array, render::mesh::{self, skinning::SkinnedMesh}};
-use bevy_mod_raycast::prelude::*;
-use itertools::Itertools;
+use std::{f32::consts::PI, marker::PhantomData};
+use bevy::{ecs::{entity::MapEntities, reflect::ReflectMapEntities, schedule::{InternedScheduleLabel, ScheduleLabel}, system::{StaticSystemParam, SystemParam}}, color::palettes::css::BLACK, math::{NormedVectorSpace, VectorSpace}, prelude::*, reflect::Array, render::mesh::{self, skinning::SkinnedMesh}};
 
-use crate::{leg, IKArm};
-#[derive(Copy, Clone, PartialEq, Default)]
+use crate::{attack::Attack, climb::{plan_climbs, ClimbSettings, Ledge}, grab::Grab, debug::{DebugCategory, DebugGizmos}, ik, dismember::{detach_severed_legs, drop_severed_legs, replan_gaits, CreatureCollapsed, DisabledLeg, LegLossResponse, LegLost, SeveredLeg}, footstep::{emit_footstep_events, FootLifted, FootPlanted}, foothold::{find_foothold, import_surface_tags, FootholdRequest, FootholdSearch, SurfaceQuery, SurfaceRules, SurfaceTag}, ground::{DefaultGround, GroundQuery}, health::Dead, inspect::ReadOnly, jump::{detect_falls, fly_creatures, handle_jumps, Airborne, AirborneLeg, Jump, Jumped, Landed, Landing, StartedFalling}, leg, lod::{baked_step_target, LocomotionLod, LodTier}, procedural::LegSkin, ragdoll::{crumple_creatures, start_crumple, Crumple, CurledLeg}, schedule::{configure_sets, LocomotionSet}, IKArm::{self, RigReady}};
+#[derive(Copy, Clone, PartialEq, Default, Debug, Reflect)]
 pub enum LegSide {
     Left,
     Right,
     #[default] None,
 }
 
-#[derive(Component)]
+#[derive(Copy, Clone, PartialEq, Debug, Reflect)]
+pub(crate) enum FootEvent {
+    Lifted,
+    Planted { impact_speed: f32 },
+}
+
+// Fields marked ReadOnly are the step in progress, editing them in an inspector does nothing useful.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct IKLeg {
     pub step_offset: Vec3,
     pub step_distance: f32,
@@ -19,9 +25,29 @@ pub struct IKLeg {
     pub step_height: f32,
     pub leg_side: LegSide,
     pub can_start_step: bool,
+    #[reflect(@ReadOnly)]
     step_start: Vec3,
+    #[reflect(@ReadOnly)]
     stepping: bool,
+    #[reflect(@ReadOnly)]
     step_elapsed: f32,
+    #[reflect(@ReadOnly)]
+    desired_pos: Option<Vec3>,
+    #[reflect(@ReadOnly)]
+    desired_surface: SurfaceTag,
+    #[reflect(@ReadOnly)]
+    desired_normal: Vec3,
+    #[reflect(@ReadOnly)]
+    surface: SurfaceTag,
+    #[reflect(@ReadOnly)]
+    normal: Vec3,
+    #[reflect(@ReadOnly)]
+    baked_pos: Option<Vec3>,
+    #[reflect(@ReadOnly)]
+    foot_event: Option<FootEvent>,
+    // Planted on the ground once its rig was ready, see place_ready_legs.
+    #[reflect(@ReadOnly)]
+    placed: bool,
 }
 
 impl IKLeg {
@@ -33,17 +59,81 @@ impl IKLeg {
         leg_side: LegSide,
         can_start_step: bool,
     ) -> Self {
-        Self { step_offset, step_distance, step_duration, step_height, leg_side, can_start_step, step_start: Vec3::ZERO, stepping: false, step_elapsed: 0. }
+        Self { step_offset, step_distance, step_duration, step_height, leg_side, can_start_step, step_start: Vec3::ZERO, stepping: false, step_elapsed: 0., desired_pos: None, desired_surface: SurfaceTag::Walkable, desired_normal: Vec3::Y, surface: SurfaceTag::Walkable, normal: Vec3::Y, baked_pos: None, foot_event: None, placed: false }
+    }
+
+    // What the foot is currently planted on.
+    pub fn surface(&self) -> SurfaceTag {
+        self.surface
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    // Whether the foot is in the air mid-step.
+    pub fn is_stepping(&self) -> bool {
+        self.stepping
+    }
+
+    // Hands over what the foot did since the last call, so a leg that stops stepping (attacking, grabbing, airborne,
+    // curled up) doesn't report its last lift or plant again every tick.
+    pub(crate) fn take_foot_event(&mut self) -> Option<FootEvent> {
+        self.foot_event.take()
+    }
+
+    // Drops a step in progress, the foot starts a fresh one from wherever it is once it rejoins the gait.
+    pub(crate) fn interrupt_step(&mut self) {
+        self.stepping = false;
     }
 }
 
-#[derive(Component)]
+// Only target_height and legs_info are parameters, the rest is worked out from the legs every tick.
+#[derive(Component, Reflect)]
+#[reflect(Component, MapEntities)]
 pub struct LegCreature {
+    #[reflect(@ReadOnly)]
     pub(crate) current_side: LegSide,
     pub target_height: f32,
+    #[reflect(@ReadOnly)]
     up: Vec3,
     pub legs_info: Vec<(Entity, Vec3)>,
+    #[reflect(@ReadOnly)]
     target_offset: Vec3,
+    #[reflect(@ReadOnly)]
+    leg_states: Vec<LegState>,
+    #[reflect(@ReadOnly)]
+    pub(crate) original_legs: usize,
+    #[reflect(@ReadOnly)]
+    pub(crate) walking_legs: usize,
+    #[reflect(@ReadOnly)]
+    pub(crate) height_scale: f32,
+    #[reflect(@ReadOnly)]
+    pub(crate) collapsed: bool,
+    // Whether the body was over its feet the last time it lost or regained a leg, see LegLossResponse.
+    #[reflect(@ReadOnly)]
+    pub(crate) supported: bool,
+    // Fraction of the body height it's crouching by, for jumps and landings.
+    #[reflect(@ReadOnly)]
+    pub(crate) crouch: f32,
+    #[reflect(@ReadOnly)]
+    pub(crate) ledge: Ledge,
+    // Extra tilt of the body on top of the support plane, towards ledges.
+    #[reflect(@ReadOnly)]
+    pub(crate) lean: Vec3,
+    // The ground under the body while fully baked, when the feet no longer touch it.
+    #[reflect(@ReadOnly)]
+    pub(crate) baked_floor: Option<Vec3>,
+}
+
+// Per-frame copy of the leg data the body systems need, kept next to the creature so they can run in parallel.
+#[derive(Copy, Clone, Reflect)]
+struct LegState {
+    target: Vec3,
+    stepping: bool,
+    leg_side: LegSide,
+    // Where the foot rests relative to the body.
+    offset: Vec3,
 }
 impl LegCreature {
     pub fn new(
@@ -51,7 +141,73 @@ impl LegCreature {
         target_height: f32,
         legs_info: Vec<(Entity, Vec3)>
     ) -> Self {
-        Self { current_side, target_height, up: Vec3::Y, legs_info, target_offset: Vec3::ZERO }
+        let leg_states = Vec::with_capacity(legs_info.len());
+        let leg_count = legs_info.len();
+        Self { current_side, target_height, up: Vec3::Y, legs_info, target_offset: Vec3::ZERO, leg_states, original_legs: leg_count, walking_legs: leg_count, height_scale: 1., collapsed: false, supported: true, crouch: 0., ledge: Ledge::None, lean: Vec3::ZERO, baked_floor: None }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.target_offset != Vec3::ZERO
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.up
+    }
+
+    // Where the creature is being steered, relative to its body.
+    pub fn target_offset(&self) -> Vec3 {
+        self.target_offset
+    }
+
+    pub fn set_target_offset(&mut self, target_offset: Vec3) {
+        self.target_offset = target_offset;
+    }
+
+    pub fn ledge(&self) -> Ledge {
+        self.ledge
+    }
+
+    // The ground right under `body`, level with the planted feet. Stepping feet are left out, the plane fitted through
+    // all of them rides up and down with the gait.
+    pub(crate) fn floor(&self, body: Vec3) -> Vec3 {
+        let (sum, planted) = self.leg_states.iter()
+            .filter(|leg| !leg.stepping)
+            .fold((Vec3::ZERO, 0), |(sum, planted), leg| (sum + leg.target, planted + 1));
+        if planted == 0 {
+            return body - self.up * self.target_height;
+        }
+        body - self.up * (body - sum / planted as f32).dot(self.up)
+    }
+
+    // Too few legs left to stand, see LegLossResponse.
+    pub fn is_collapsed(&self) -> bool {
+        self.collapsed
+    }
+}
+
+// Points legs_info at the respawned legs when the creature is loaded from a scene.
+impl MapEntities for LegCreature {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for (leg_entity, _) in &mut self.legs_info {
+            *leg_entity = entity_mapper.map_entity(*leg_entity);
+        }
+    }
+}
+
+// On every leg, pointing back at the creature it walks for. Added to the legs in legs_info when the creature is
+// spawned, spawn a leg with it to attach it to a creature that's already walking. Removing it, or IKLeg, or
+// despawning the leg takes the leg out of legs_info and sends LegLost.
+#[derive(Component, Reflect, Copy, Clone, Debug)]
+#[reflect(Component, MapEntities)]
+pub struct LegOf {
+    pub creature: Entity,
+    // Where the leg hangs off the body, copied into legs_info.
+    pub offset: Vec3,
+}
+
+impl MapEntities for LegOf {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.creature = entity_mapper.map_entity(self.creature);
     }
 }
 
@@ -59,22 +215,199 @@ impl LegCreature {
 pub struct LegCreatureVisual {
 }
 
-pub struct LegPlugin;
+// Steers a LegCreature with WASD/QE. Without it, creatures go wherever `set_target_offset` points them.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct KeyboardMovement;
+
+// Generic over the ground query backend, LegPlugin::new() uses the one picked by cargo features.
+pub struct LegPlugin<G = DefaultGround> {
+    schedule: InternedScheduleLabel,
+    marker: PhantomData<fn() -> G>,
+}
+
+impl LegPlugin {
+    pub fn new() -> Self {
+        Self::with_ground()
+    }
+}
 
-impl Plugin for LegPlugin {
+impl Default for LegPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> LegPlugin<G> {
+    pub fn with_ground() -> Self {
+        Self { schedule: FixedUpdate.intern(), marker: PhantomData }
+    }
+
+    // Runs the systems in another schedule, e.g. FixedUpdate or PostUpdate. See LocomotionSet.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl<G: SystemParam + 'static> Plugin for LegPlugin<G>
+where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (handle_height, handle_visual, determine_side, handle_leg_creature, handle_legs, move_creature).chain())
-        .observe(setup_legs);
+        let schedule = self.schedule;
+        configure_sets(app, schedule);
+        app.register_type::<IKLeg>()
+        .register_type::<LegCreature>()
+        .register_type::<LegOf>()
+        .register_type::<KeyboardMovement>()
+        .register_type::<SurfaceTag>()
+        .register_type::<FootholdSearch>()
+        .register_type::<ClimbSettings>()
+        .register_type::<Jump>()
+        .register_type::<Landing>()
+        .register_type::<Airborne>()
+        .register_type::<AirborneLeg>()
+        .register_type::<DisabledLeg>()
+        .register_type::<SeveredLeg>()
+        .register_type::<LegLossResponse>()
+        .register_type::<Crumple>()
+        .register_type::<CurledLeg>()
+        .register_type::<LegSkin>()
+        .add_systems(schedule, move_creature.in_set(LocomotionSet::Input))
+        .add_systems(schedule, (detach_severed_legs, link_legs, place_ready_legs::<G>, replan_gaits, determine_side, handle_leg_creature, plan_climbs::<G>).chain().in_set(LocomotionSet::GaitPlanning))
+        .add_systems(schedule, (find_leg_targets::<G>, advance_legs, emit_footstep_events).chain().in_set(LocomotionSet::FootPlacement))
+        .add_systems(schedule, (
+            detect_falls::<G>,
+            handle_jumps,
+            fly_creatures::<G>,
+            start_crumple,
+            crumple_creatures::<G>,
+            drop_severed_legs::<G>,
+            sample_baked_floors::<G>,
+            sync_leg_states,
+            handle_height,
+            handle_visual,
+        ).chain().in_set(LocomotionSet::BodyPose))
+        .add_event::<FootLifted>()
+        .add_event::<FootPlanted>()
+        .add_event::<LegLost>()
+        .add_event::<CreatureCollapsed>()
+        .add_event::<Jumped>()
+        .add_event::<StartedFalling>()
+        .add_event::<Landed>()
+        .init_resource::<SurfaceRules>()
+        .add_systems(Update, import_surface_tags)
+        .observe(unlink_removed_leg)
+        .observe(unlink_leg)
+        .observe(unlink_creature_legs);
+        #[cfg(feature = "debug")]
+        app.add_systems(schedule, draw_leg_debug.after(advance_legs).in_set(LocomotionSet::FootPlacement));
     }
 }
 
-fn setup_legs(
-    trigger: Trigger<OnAdd, IKLeg>,
-    mut leg_query: Query<(&GlobalTransform, &mut IKArm::IKArm, &IKLeg)>
-  ) {
-    let Ok((transform, mut arm, leg)) = leg_query.get_mut(trigger.entity()) else {return;};
-    arm.target = transform.translation() + leg.step_offset;
-    //println!("SETUP LEG");
+// Puts each foot on the ground under its rest position once the leg's rig is in, its pose doesn't mean anything
+// before that. Legs loaded from a scene keep the foot they were saved with.
+fn place_ready_legs<G: SystemParam>(
+    mut ready_events: EventReader<RigReady>,
+    mut leg_query: Query<(&mut IKArm::IKArm, &mut IKLeg)>,
+    transform_helper: TransformHelper,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let filter = |entity: Entity| !surfaces.is_creature_part(entity);
+    for ready in ready_events.read() {
+        let Ok((mut arm, mut leg)) = leg_query.get_mut(ready.arm) else {continue;};
+        if leg.placed {
+            continue;
+        }
+        leg.placed = true;
+        let Ok(root) = transform_helper.compute_global_transform(ready.arm) else {continue;};
+        let Ok(down) = Dir3::new(-arm.up) else {continue;};
+        // From hip height straight down, as far as the leg reaches.
+        let rest = root.translation() + leg.step_offset.reject_from(arm.up);
+        arm.target = rest + leg.step_offset.project_onto(arm.up);
+        let Some(hit) = ground.cast_ground_ray(Ray3d { origin: rest, direction: down }, ready.reach, &filter) else {continue;};
+        arm.target = hit.position;
+        leg.normal = hit.normal;
+    }
+}
+
+// Links new creatures and new legs up with each other. Done here rather than in an observer, scenes insert
+// components before their entities are mapped and the references would still point into the saved world.
+fn link_legs(
+    mut commands: Commands,
+    mut creature_query: Query<(Entity, &mut LegCreature)>,
+    new_leg_query: Query<(Entity, &LegOf), Added<LegOf>>,
+    leg_query: Query<Has<LegOf>, With<IKLeg>>,
+    mut lost_events: EventWriter<LegLost>,
+) {
+    for (creature_entity, mut leg_creature) in creature_query.iter_mut() {
+        if !leg_creature.is_added() {
+            continue;
+        }
+        leg_creature.legs_info.retain(|(leg_entity, offset)| {
+            let Ok(linked) = leg_query.get(*leg_entity) else {
+                warn!("Leg {leg_entity} of creature {creature_entity} isn't a leg, dropping it");
+                lost_events.send(LegLost { creature: creature_entity, leg: *leg_entity });
+                return false;
+            };
+            if !linked {
+                commands.entity(*leg_entity).try_insert(LegOf { creature: creature_entity, offset: *offset });
+            }
+            true
+        });
+    }
+    for (leg_entity, leg_of) in new_leg_query.iter() {
+        let Ok((_, mut leg_creature)) = creature_query.get_mut(leg_of.creature) else {
+            warn!("Leg {leg_entity} belongs to {}, which isn't a LegCreature", leg_of.creature);
+            commands.entity(leg_entity).remove::<LegOf>();
+            continue;
+        };
+        if leg_creature.legs_info.iter().any(|(entity, _)| *entity == leg_entity) {
+            continue;
+        }
+        leg_creature.legs_info.push((leg_entity, leg_of.offset));
+        leg_creature.original_legs = leg_creature.original_legs.max(leg_creature.legs_info.len());
+    }
+}
+
+fn unlink_removed_leg(
+    trigger: Trigger<OnRemove, IKLeg>,
+    mut commands: Commands,
+) {
+    commands.entity(trigger.entity()).remove::<LegOf>();
+}
+
+fn unlink_leg(
+    trigger: Trigger<OnRemove, LegOf>,
+    leg_query: Query<&LegOf>,
+    mut creature_query: Query<&mut LegCreature>,
+    mut lost_events: EventWriter<LegLost>,
+) {
+    let leg_entity = trigger.entity();
+    let Ok(leg_of) = leg_query.get(leg_entity) else {return;};
+    let Ok(mut leg_creature) = creature_query.get_mut(leg_of.creature) else {return;};
+    let leg_count = leg_creature.legs_info.len();
+    leg_creature.legs_info.retain(|(entity, _)| *entity != leg_entity);
+    if leg_creature.legs_info.len() != leg_count {
+        lost_events.send(LegLost { creature: leg_of.creature, leg: leg_entity });
+    }
+}
+
+// Legs outlive their creature, they just stop pointing at it.
+fn unlink_creature_legs(
+    trigger: Trigger<OnRemove, LegCreature>,
+    mut commands: Commands,
+    creature_query: Query<&LegCreature>,
+) {
+    let Ok(leg_creature) = creature_query.get(trigger.entity()) else {return;};
+    for (leg_entity, _) in &leg_creature.legs_info {
+        let Some(mut leg) = commands.get_entity(*leg_entity) else {continue;};
+        leg.remove::<LegOf>();
+    }
 }
 
 /*
@@ -98,9 +431,10 @@ fn handle_height(
  */
 
 fn move_creature(
-    mut creature_query: Query<&mut LegCreature>,
-    keys: Res<ButtonInput<KeyCode>>,
+    mut creature_query: Query<&mut LegCreature, With<KeyboardMovement>>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
 ) {
+    let Some(keys) = keys else {return;};
     for (mut creature) in creature_query.iter_mut() {
         let mut vec = Vec3::ZERO;
         if keys.pressed(KeyCode::KeyW) {
@@ -127,95 +461,141 @@ fn move_creature(
     }
 }
 
+// Bodies that lean themselves, not the dead.
+type LeaningBodies = (Without<LegCreatureVisual>, Without<Crumple>);
+
 fn handle_visual(
-    mut leg_creature_query: Query<(Entity, &mut Transform, &LegCreature), Without<LegCreatureVisual>>,
-    children_query: Query<(&Children)>,
+    mut leg_creature_query: Query<(&mut Transform, &LegCreature), LeaningBodies>,
 ) {
-    for  (creature_entity, mut transform, leg_creature) in leg_creature_query.iter_mut() {
-        let target = transform.aligned_by(Vec3::Y, leg_creature.up, Vec3::X, transform.local_x());
+    leg_creature_query.par_iter_mut().for_each(|(mut transform, leg_creature)| {
+        let up = (leg_creature.up + leg_creature.lean).try_normalize().unwrap_or(leg_creature.up);
+        let target = transform.aligned_by(Vec3::Y, up, Vec3::X, transform.local_x());
         transform.rotation = transform.rotation.slerp(target.rotation, 0.05);
-        let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
-        let (a, b, c) = target.rotation.to_euler(EulerRot::XYZ);
-        //println!("normal: {}, rota: {}, target: {}", leg_creature.up, Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees()), Vec3::new(a.to_degrees(), b.to_degrees(), c.to_degrees()));
+    });
+}
+
+// Legs busy attacking or grabbing aren't walked on.
+type WalkingLegs = (Without<DisabledLeg>, Without<Attack>, Without<Grab>);
+
+fn sync_leg_states(
+    mut leg_creature_query: Query<&mut LegCreature>,
+    leg_query: Query<(&IKArm::IKArm, &IKLeg), WalkingLegs>,
+) {
+    leg_creature_query.par_iter_mut().for_each(|mut leg_creature| {
+        let leg_creature = &mut *leg_creature;
+        // Cleared rather than rebuilt so the buffer keeps its capacity across frames.
+        leg_creature.leg_states.clear();
+        leg_creature.leg_states.extend(leg_creature.legs_info.iter()
+            .filter_map(|(leg_entity, leg_offset)| leg_query.get(*leg_entity).ok().map(|legs| (legs, *leg_offset)))
+            .map(|((arm, leg), leg_offset)| LegState { target: arm.target, stepping: leg.stepping, leg_side: leg.leg_side, offset: leg_offset + leg.step_offset }));
+    });
+}
+
+type OnItsFeet = (Without<Crumple>, Without<Airborne>);
+
+// One ray straight down from the body of each fully baked creature, so it keeps to the ground without its feet.
+fn sample_baked_floors<G: SystemParam>(
+    mut leg_creature_query: Query<(&GlobalTransform, &mut LegCreature, &LocomotionLod), OnItsFeet>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let filter = |entity: Entity| !surfaces.is_creature_part(entity);
+    for (transform, mut leg_creature, lod) in leg_creature_query.iter_mut() {
+        if lod.baked_blend() < 1. {
+            leg_creature.baked_floor = None;
+            continue;
+        }
+        if !lod.should_update() && leg_creature.baked_floor.is_some() {
+            continue;
+        }
+        let Ok(down) = Dir3::new(-leg_creature.up) else {continue;};
+        // From as high above the body as it stands off the ground, to catch slopes rising under it.
+        let height = leg_creature.target_height * leg_creature.height_scale;
+        let origin = transform.translation() + leg_creature.up * height;
+        if let Some(hit) = ground.cast_ground_ray(Ray3d { origin, direction: down }, height * 3., &filter) {
+            leg_creature.baked_floor = Some(hit.position);
+        }
     }
 }
 
 fn handle_height(
-    mut leg_creature_query: Query<(Entity, &mut Transform, &mut LegCreature)>,
-    mut leg_query: Query<(&IKArm::IKArm, &Name)>,
-    mut gizmos: Gizmos,
+    mut leg_creature_query: Query<(&mut Transform, &mut LegCreature, Option<&LocomotionLod>), OnItsFeet>,
+    time: Res<Time>,
 ) {
-    'outer: for (creature_entity, mut transform, mut leg_creature) in leg_creature_query.iter_mut() {
-        let mut normal_total = Vec3::ZERO;
-        let mut pos_total = Vec3::ZERO;
-        let mut i = 0;
-        for v in leg_creature.legs_info.iter().combinations(3) {
-            let legs= [v[0].0, v[1].0, v[2].0];
-            let Ok([(a, a_name), (b, b_name), (c, c_name)]) = leg_query.get_many_mut(legs) else {continue;};
-            //println!("{} {} {}", a_name, b_name, c_name);
-            let v1 = a.target;
-            let v2 = b.target;
-            let v3 = c.target; 
-            if (v1 == v2 || v2 == v3 || v1.is_nan() || v2.is_nan() || v3.is_nan()) {
-                continue 'outer;
+    let delta = time.delta_seconds();
+    leg_creature_query.par_iter_mut().for_each(|(mut transform, mut leg_creature, lod)| {
+        let height = leg_creature.target_height * leg_creature.height_scale * (1. - leg_creature.crouch);
+        if let Some(lod) = lod.filter(|lod| lod.baked_blend() >= 1.) {
+            // Baked feet don't touch the ground, so there's no plane to stand on. The body glides along at the pace of
+            // the baked cycle and keeps its height over the floor sampled under it.
+            let heading = (transform.rotation * leg_creature.target_offset).reject_from(leg_creature.up).normalize_or_zero();
+            transform.translation += heading * lod.baked_speed() * delta;
+            if let Some(floor) = leg_creature.baked_floor {
+                let up = leg_creature.up;
+                let target = transform.translation - up * (transform.translation - floor).dot(up) + up * height;
+                transform.translation = transform.translation.lerp(target, 0.1);
             }
-            let (plane, pos) = InfinitePlane3d::from_points(v1, v2, v3);
-            normal_total += *plane.normal;
-            pos_total += pos;
-            i += 1;
+            return;
+        }
+        if lod.is_some_and(|lod| !lod.should_update()) {
+            return;
         }
-        let normal_average = (normal_total / i as f32).normalize();
-        let pos_average = pos_total / i as f32;
-        //transform.translation.y = transform.translation.y.lerp(pos_average.y + leg_creature.target_height, 0.1);
+        if leg_creature.collapsed {
+            // No plane to stand on anymore, the body just sinks onto whatever feet are left.
+            let Some(feet) = average_foot(&leg_creature.leg_states) else {return;};
+            let target = feet + leg_creature.up * height;
+            transform.translation = transform.translation.lerp(target, 0.1);
+            return;
+        }
+        let Some((normal_average, pos_average)) = ik::fit_plane_by(&leg_creature.leg_states, leg_creature.up, |leg| leg.target) else {return;};
         let mut target_transform = *transform;
-        target_transform.translation = pos_average;
+        // The feet left after losing a leg aren't centred on the body, and standing over their middle would walk it
+        // off sideways as they keep stepping back under it.
+        let rest_centre = leg_creature.leg_states.iter().map(|leg| leg.offset).sum::<Vec3>() / leg_creature.leg_states.len() as f32;
+        target_transform.translation = pos_average - transform.rotation * rest_centre.reject_from(Vec3::Y);
 
-        let target = target_transform.transform_point(Vec3::Y * leg_creature.target_height);
-       // gizmos.line(transform.translation, transform.transform_point(Vec3::Y * leg_creature.target_height * 10.), BLACK);
+        let target = target_transform.transform_point(Vec3::Y * height);
         transform.translation = transform.translation.lerp(target, 0.1);
-      //  println!("{}", transform.translation);
         if (!normal_average.is_nan()) {
             leg_creature.up = normal_average;
         }
-    };     
+    });
+}
+
+fn average_foot(leg_states: &[LegState]) -> Option<Vec3> {
+    if leg_states.is_empty() {
+        return None;
+    }
+    Some(leg_states.iter().map(|leg| leg.target).sum::<Vec3>() / leg_states.len() as f32)
 }
 
 fn determine_side(
-    leg_query: Query<(&IKLeg)>,
-    mut leg_creature_query: Query<(Entity, &mut LegCreature)>,
+    mut leg_creature_query: Query<&mut LegCreature>,
 ) {
-    for (creature_entity, mut leg_creature) in leg_creature_query.iter_mut() {
-        let mut left_side_moving = false;
-        let mut right_side_moving = false;
-        for (leg_entity, leg_offset) in &leg_creature.legs_info {
-            let Ok((mut leg)) = leg_query.get(*leg_entity) else {continue;};
-            if leg.stepping {
-                match leg.leg_side {
-                    LegSide::Left => left_side_moving = true,
-                    LegSide::Right => right_side_moving = true,
-                    LegSide::None => {},
-                }
-            }
-        }
-        if (!left_side_moving && !right_side_moving) {
+    leg_creature_query.par_iter_mut().for_each(|mut leg_creature| {
+        let side_moving = |side| leg_creature.leg_states.iter().any(|leg| leg.stepping && leg.leg_side == side);
+        if (!side_moving(LegSide::Left) && !side_moving(LegSide::Right)) {
             if leg_creature.current_side == LegSide::Left {
                 leg_creature.current_side = LegSide::Right;
             } else {
                 leg_creature.current_side = LegSide::Left;
             }
         }
-    }
+    });
 }
 
 fn handle_leg_creature(
     mut leg_query: Query<(&mut IKLeg, &mut Transform)>,
-    leg_creature_query: Query<(Entity, &LegCreature, &GlobalTransform)>,
+    leg_creature_query: Query<(&LegCreature, &GlobalTransform, Has<Dead>), Without<Crumple>>,
 ) {
-    for (creature_entity, mut leg_creature, leg_creature_transform) in leg_creature_query.iter() {
+    for (leg_creature, leg_creature_transform, dead) in leg_creature_query.iter() {
         for (leg_entity, leg_offset) in &leg_creature.legs_info {
             let Ok((mut leg, mut leg_transform)) = leg_query.get_mut(*leg_entity) else {continue;};
             leg_transform.translation = leg_creature_transform.translation() + *leg_offset;
-            if (leg.leg_side == leg_creature.current_side) {
+            // Dead creatures that stand their ground finish the steps they're in and take no more.
+            if (leg.leg_side == leg_creature.current_side && !leg_creature.collapsed && !dead) {
                 leg.can_start_step = true;
             } else {
                 leg.can_start_step = false;
@@ -224,122 +604,150 @@ fn handle_leg_creature(
     }
 }
 
-fn handle_legs(
-    leg_creature_query: Query<(Entity, &LegCreature, &GlobalTransform)>,
-    mut leg_query: Query<(&GlobalTransform, &mut IKArm::IKArm, &mut IKLeg)>,
-    mut raycast: Raycast,
-    mut gizmos: Gizmos,
-    time: Res<Time>,
-) {
-    //let group = get_highest_distance_group(&leg_query);
-    for (creature_entity, mut leg_creature, leg_creature_transform) in leg_creature_query.iter() {
+type FootholdSearcher = (&'static LegCreature, &'static GlobalTransform, Option<&'static FootholdSearch>, Option<&'static ClimbSettings>);
+
+// Ground queries need exclusive access to the raycaster, so this is the one serial pass. It only
+// decides where each foot wants to go, the stepping itself happens in parallel in advance_legs.
+fn find_leg_targets<G: SystemParam>(
+    leg_creature_query: Query<FootholdSearcher, OnItsFeet>,
+    mut leg_query: Query<(&IKArm::IKArm, &mut IKLeg, Option<&LocomotionLod>, Has<DisabledLeg>)>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+    mut debug_gizmos: DebugGizmos,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let default_search = FootholdSearch::default();
+    let default_climb = ClimbSettings::default();
+    for (leg_creature, leg_creature_transform, search, climb) in leg_creature_query.iter() {
+        let search = leg_creature.ledge.adjust_search(search.unwrap_or(&default_search));
+        let search = &search;
+        let max_climb_height = climb.unwrap_or(&default_climb).max_climb_height;
+        let floor = leg_creature.floor(leg_creature_transform.translation());
+        let new_pos = leg_creature_transform.transform_point(leg_creature.target_offset);
+        // Feet stay put in front of anything the creature can't get over.
+        let new_diff = if matches!(leg_creature.ledge, Ledge::Blocked { .. }) { Vec3::ZERO } else { new_pos - leg_creature_transform.translation() };
         for (leg_entity, leg_offset) in &leg_creature.legs_info {
-            let Ok((transform,mut arm, mut leg)) = leg_query.get_mut(*leg_entity) else {continue;};
-            //let mut desired_pos: Vec3 = transform.translation() + leg.step_offset;
-            let mut custom = Transform::from(*leg_creature_transform);
-            let new_pos = leg_creature_transform.transform_point(leg_creature.target_offset);
-            let new_diff = new_pos - leg_creature_transform.translation();
-            gizmos.line(leg_creature_transform.translation(), leg_creature_transform.translation() + new_diff * 2., Color::linear_rgb(1., 0., 0.));
-            //let target_transform = *leg_creature_transform;
-           // let target_transform = target_transform.compute_transform() +
-            let mut desired_pos: Vec3 = leg_creature_transform.transform_point(*leg_offset + leg.step_offset) + new_diff;
-            /* 
-            custom.translation = desired_pos;
-            custom.translation = custom.transform_point(Vec3::Y * 1.);
-            //custom.translation -= (custom.translation - leg_creature_transform.translation()).normalize() / 2.;
-            //println!("actual: {}, new: {}, offset: {}", desired_pos, new_desired_pos, leg.step_offset);
-            //println!("old: {}, new: {}, offset: {}", old_desired_pos, desired_pos, leg.step_offset);
-            //let mut origin = (leg_creature_transform.transform_point(Vec3::Y * 1.));
-            let origin = custom.translation - (custom.translation - leg_creature_transform.translation()).normalize() / 2.;
-            let origin2 = custom.translation + (custom.translation - leg_creature_transform.translation()).normalize() / 2.;
-            let dir = (desired_pos - origin).normalize();
-            //let ray = Ray3d::new(desired_pos + Vec3::Y, Vec3::NEG_Y);
-            let ray = Ray3d::new(origin, dir);
-            let ray2 = Ray3d::new(origin2, (desired_pos - origin2).normalize());
-            let hits: &[(Entity, IntersectionData)] = raycast.debug_cast_ray(ray, &RaycastSettings::default().with_filter(&|entity| entity != creature_entity && entity != *leg_entity), &mut gizmos);
-            if let Some((hit, hit_data)) = hits.first() {
-            //   println!("{}", hit_data.position().y);
-                if (hit_data.distance() < 2.) {
-                    arm.up = hit_data.normal();
-                    desired_pos = hit_data.position();
-                } else {
-                    desired_pos = arm.target
-                }
-            } else {
-                desired_pos = arm.target;
+            let Ok((arm, mut leg, lod, disabled)) = leg_query.get_mut(*leg_entity) else {continue;};
+            if disabled {
+                // Tucked in under the body, the hip sits lower than the body height and a foot hanging straight down
+                // from it would go through the ground.
+                leg.desired_pos = Some(leg_creature_transform.translation() + *leg_offset * 0.5 - leg_creature.up * leg_creature.target_height * 0.25);
+                continue;
             }
-            let hits2: &[(Entity, IntersectionData)] = raycast.debug_cast_ray(ray2, &RaycastSettings::default().with_filter(&|entity| entity != creature_entity && entity != *leg_entity), &mut gizmos);
-            */
-            if let Some(pos) = find_step(Transform::from(*leg_creature_transform), desired_pos, &mut raycast, RaycastSettings::default().with_filter(&|entity| entity != creature_entity && entity != *leg_entity), &mut gizmos) {
-                desired_pos = pos;
-            } else {
-                desired_pos = arm.target;
+            let draw_debug = lod.is_none_or(|lod| lod.draws_debug());
+            let nominal_pos = leg_creature_transform.transform_point(*leg_offset + leg.step_offset);
+            leg.baked_pos = lod.map(|lod| baked_step_target(nominal_pos, leg_creature.up, new_diff.normalize_or_zero(), &leg, lod.baked_phase()));
+            if lod.is_some_and(|lod| lod.baked_blend() >= 1.) {
+                continue;
             }
-            /*
-            if let Some((hit, hit_data)) = hits2.first() {
-                //   println!("{}", hit_data.position().y);
-                    if (hit_data.distance() < 2.) {
-                        arm.up = hit_data.normal();
-                        desired_pos = hit_data.position();
-                    } else {
-                        desired_pos = arm.target
-                    }
-                } else {
-                    desired_pos = arm.target;
-                }
- */
-
-            let distance = arm.target.distance(desired_pos);
-            //println!("{}", distance);
-            if (!leg.stepping) {
-                if (distance > leg.step_distance && leg.can_start_step) {
-                    leg.stepping = true;
-                    leg.step_elapsed = 0.;
-                    leg.step_start = arm.target;
-                }
-            } else {
-                let step_progress = leg.step_elapsed / leg.step_duration;
-                arm.target = leg.step_start.lerp(desired_pos, leg.step_elapsed / leg.step_duration);
-                let y_offset = (1. - ((step_progress * 2.) - 1.).abs()) * leg.step_height;
-                arm.target.y = leg.step_start.y + y_offset;
-                leg.step_elapsed += time.delta_seconds();
-                if (leg.step_elapsed >= leg.step_duration) {
-                    arm.target = desired_pos;
-
-                    leg.stepping = false;
-                }
+            if lod.is_some_and(|lod| !lod.should_update()) && leg.desired_pos.is_some() {
+                continue;
             }
+            let ray_count = lod.map_or(LodTier::Full, |lod| lod.tier).ray_count();
+            // Shorter strides on slippery ground, based on what the foot is standing on now.
+            let lead = new_diff * surfaces.rule(leg.surface).step_length_scale;
+            let request = FootholdRequest { search, transform: leg_creature_transform, up: leg_creature.up, desired_pos: nominal_pos + lead, ray_budget: ray_count };
+            let foothold = find_foothold(&mut *ground, &surfaces, &request, debug_gizmos.gizmos(DebugCategory::Raycasts).filter(|_| draw_debug))
+                // Feet reach further ahead than plan_climbs looks, and the fallback search finds the tops of walls.
+                .filter(|foothold| (foothold.position - floor).dot(leg_creature.up) <= max_climb_height);
+            // Nothing in reach keeps the last foothold, the foot may be mid-step and would otherwise plant in the air.
+            leg.desired_pos = Some(foothold.map_or(leg.desired_pos.unwrap_or(arm.target), |foothold| foothold.position));
+            leg.desired_surface = foothold.map_or(leg.surface, |foothold| foothold.surface);
+            leg.desired_normal = foothold.map_or(leg.normal, |foothold| foothold.normal);
         }
     }
 }
 
-fn find_step(
-    transform: Transform,
-    desired_pos: Vec3,
-    raycast: &mut Raycast,
-    raycast_settings: RaycastSettings,
-    mut gizmos: &mut Gizmos
-) -> Option<Vec3> {
-    let mut custom = Transform::from(transform);
-    custom.translation = desired_pos;
-    custom.translation = custom.transform_point(Vec3::Y * 1.);
-    let origin = custom.translation - (custom.translation - transform.translation).normalize() * 0.75;
-    let origin2 = custom.translation + (custom.translation - transform.translation).normalize() * 1.5;
-    let ray = Ray3d::new(origin, (desired_pos - origin).normalize());
-    let ray2 = Ray3d::new(origin2, (desired_pos - origin2).normalize());
-    raycast.debug_cast_ray(ray2, &raycast_settings, &mut gizmos);
-    if let Some((hit, hit_data)) = raycast.debug_cast_ray(ray, &raycast_settings, &mut gizmos).first() {
-        if (hit_data.distance() < 1.5) {
-            return Some(hit_data.position());
+type SteppingLeg = (&'static mut IKArm::IKArm, &'static mut IKLeg, Option<&'static LocomotionLod>, Has<DisabledLeg>);
+// Curled, airborne and busy legs are posed by their own systems.
+type FreeLegs = (Without<CurledLeg>, Without<AirborneLeg>, Without<Attack>, Without<Grab>);
+
+fn advance_legs(
+    mut leg_query: Query<SteppingLeg, FreeLegs>,
+    surface_rules: Res<SurfaceRules>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    leg_query.par_iter_mut().for_each(|(mut arm, mut leg, lod, disabled)| {
+        if disabled {
+            leg.stepping = false;
+            if let Some(desired_pos) = leg.desired_pos {
+                arm.target = arm.target.lerp(desired_pos, 0.1);
+            }
+            return;
         }
-    }
-    if let Some((hit, hit_data)) = raycast.debug_cast_ray(ray2, &raycast_settings, &mut gizmos).first() {
-        if (hit_data.distance() < 4.) {
-            return Some(hit_data.position());
+        let baked_blend = lod.map_or(0., |lod| lod.baked_blend());
+        if let Some(baked_pos) = leg.baked_pos.filter(|_| baked_blend >= 1.) {
+            arm.target = baked_pos;
+            leg.stepping = false;
+            return;
+        }
+        let Some(desired_pos) = leg.desired_pos else {return;};
+        let distance = arm.target.distance(desired_pos);
+        if (!leg.stepping) {
+            if (distance > leg.step_distance && leg.can_start_step) {
+                leg.stepping = true;
+                leg.step_elapsed = 0.;
+                leg.step_start = arm.target;
+                leg.foot_event = Some(FootEvent::Lifted);
+            }
+        } else {
+            // Sticky ground slows the foot down as it pulls free.
+            let step_duration = leg.step_duration * surface_rules.get(leg.surface).step_duration_scale;
+            let step_progress = leg.step_elapsed / step_duration;
+            arm.target = leg.step_start.lerp(desired_pos, step_progress);
+            // Rises or drops towards the new foothold along the way, so ledges don't snap the foot at the end.
+            let y_offset = (1. - ((step_progress * 2.) - 1.).abs()) * leg.step_height;
+            arm.target.y += y_offset;
+            leg.step_elapsed += delta;
+            if (leg.step_elapsed >= step_duration) {
+                let impact_speed = if delta > 0. { arm.target.distance(desired_pos) / delta } else { 0. };
+                arm.target = desired_pos;
+                leg.surface = leg.desired_surface;
+                leg.normal = leg.desired_normal;
+                leg.stepping = false;
+                leg.foot_event = Some(FootEvent::Planted { impact_speed });
+            }
+        }
+        if let Some(baked_pos) = leg.baked_pos.filter(|_| baked_blend > 0.) {
+            arm.target = arm.target.lerp(baked_pos, baked_blend);
+        }
+    });
+}
+
+#[cfg(feature = "debug")]
+fn draw_leg_debug(
+    leg_creature_query: Query<(&LegCreature, &GlobalTransform, Option<&LocomotionLod>)>,
+    leg_query: Query<(&IKArm::IKArm, &IKLeg)>,
+    mut debug_gizmos: DebugGizmos,
+) {
+    for (leg_creature, leg_creature_transform, lod) in leg_creature_query.iter() {
+        if lod.is_some_and(|lod| !lod.draws_debug()) {
+            continue;
+        }
+        let body = leg_creature_transform.translation();
+        let new_diff = leg_creature_transform.transform_point(leg_creature.target_offset) - body;
+        debug_gizmos.line(DebugCategory::StepTargets, body, body + new_diff * 2., Color::linear_rgb(1., 0., 0.));
+        debug_gizmos.line(DebugCategory::BodyPlane, body, body + leg_creature.up * 0.5, Color::linear_rgb(0., 1., 1.));
+        if let (Some(gizmos), Ok(normal)) = (debug_gizmos.gizmos(DebugCategory::BodyPlane), Dir3::new(leg_creature.up)) {
+            gizmos.circle(body - leg_creature.up * leg_creature.target_height, normal, 0.3, Color::linear_rgb(0., 1., 1.));
+        }
+        for (leg_entity, _) in &leg_creature.legs_info {
+            let Ok((arm, leg)) = leg_query.get(*leg_entity) else {continue;};
+            if let Some(desired_pos) = leg.desired_pos {
+                debug_gizmos.sphere(DebugCategory::StepTargets, desired_pos, 0.03, Color::linear_rgb(1., 0., 0.));
+            }
+            let phase_color = if leg.stepping {
+                Color::linear_rgb(1., 1., 0.)
+            } else if leg.can_start_step {
+                Color::linear_rgb(0., 1., 0.)
+            } else {
+                Color::linear_rgb(0., 0., 1.)
+            };
+            debug_gizmos.sphere(DebugCategory::GaitPhases, arm.target, 0.04, phase_color);
         }
     }
-    //println!("Found nothing");
-    return None;
 }
 
 fn get_highest_distance_group(
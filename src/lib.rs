@@ -0,0 +1,116 @@
+// Procedural legs for Bevy: IK arms, stepping gaits, and the creature behaviours built on top of them.
+// Add `LocomotionPlugin::new()` and spawn creatures with `spider::spawn_spider`, or your own IKArm/IKLeg/LegCreature rigs.
+// `procedural` builds skinned legs in code for rigs that don't come from Blender.
+#![allow(non_snake_case)]
+
+use std::marker::PhantomData;
+use bevy::{ecs::{schedule::{InternedScheduleLabel, ScheduleLabel}, system::SystemParam}, prelude::*};
+
+pub mod IKArm;
+pub mod attack;
+pub mod climb;
+pub mod debug;
+pub mod dismember;
+pub mod foothold;
+pub mod footstep;
+pub mod grab;
+pub mod ground;
+pub mod headless;
+pub mod health;
+pub mod ik;
+pub mod inspect;
+pub mod interpolation;
+pub mod jump;
+pub mod leg;
+pub mod lod;
+pub mod perception;
+pub mod procedural;
+pub mod ragdoll;
+pub mod schedule;
+pub mod spider;
+
+use attack::AttackPlugin;
+use debug::DebugDrawPlugin;
+use grab::GrabPlugin;
+use ground::{DefaultGround, GroundQuery};
+use health::HealthPlugin;
+use interpolation::InterpolationPlugin;
+use leg::LegPlugin;
+use lod::LodPlugin;
+use perception::PerceptionPlugin;
+use IKArm::IKArmPlugin;
+
+pub mod prelude {
+    pub use crate::{
+        attack::{Attack, AttackHit, AttackPhase},
+        climb::{ClimbSettings, Ledge},
+        debug::{DebugCategory, DebugDrawConfig},
+        dismember::{CreatureCollapsed, DisabledLeg, LegLossResponse, LegLost, SeveredLeg},
+        foothold::{FootholdSearch, SurfaceRule, SurfaceRules, SurfaceTag},
+        footstep::{FootLifted, FootPlanted, FootstepAudioPlugin, FootstepSounds},
+        grab::{Carried, Grab, Grabbed, Released},
+        ground::{DefaultGround, GroundHit, GroundQuery, Heightfield},
+        interpolation::SimTransform,
+        health::{CreatureDied, Damage, DamageTaken, DeathPose, DeathResponse, Health, HitQuery, HitZone, LegDestroyed},
+        jump::{Airborne, Jump, Jumped, Landed, Landing, StartedFalling},
+        leg::{IKLeg, KeyboardMovement, LegCreature, LegOf, LegSide},
+        lod::{LocomotionLod, LodSettings, LodTier},
+        schedule::LocomotionSet,
+        perception::{Perceivable, Perception, PerceptionSettings, TargetHeard, TargetLost, TargetSeen},
+        procedural::{LegAssets, LegShape},
+        spider::{spawn_procedural_spider, spawn_spider},
+        IKArm::{ArmRig, IKArm, IKArmTarget, RigReady},
+        LocomotionPlugin,
+    };
+}
+
+// Everything in one plugin, generic over the ground query backend like LegPlugin. Perception and damage always run
+// in Update, the locomotion pipeline runs on the fixed tick (with interpolated visuals) unless moved with `in_schedule`.
+pub struct LocomotionPlugin<G = DefaultGround> {
+    schedule: InternedScheduleLabel,
+    marker: PhantomData<fn() -> G>,
+}
+
+impl LocomotionPlugin {
+    pub fn new() -> Self {
+        Self::with_ground()
+    }
+}
+
+impl Default for LocomotionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> LocomotionPlugin<G> {
+    pub fn with_ground() -> Self {
+        Self { schedule: FixedUpdate.intern(), marker: PhantomData }
+    }
+
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl<G: SystemParam + 'static> Plugin for LocomotionPlugin<G>
+where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            IKArmPlugin::new().in_schedule(self.schedule),
+            LegPlugin::<G>::with_ground().in_schedule(self.schedule),
+            LodPlugin::new().in_schedule(self.schedule),
+            PerceptionPlugin::<G>::with_ground(),
+            HealthPlugin,
+            AttackPlugin::new().in_schedule(self.schedule),
+            GrabPlugin::<G>::with_ground().in_schedule(self.schedule),
+            DebugDrawPlugin,
+        ));
+        if self.schedule == FixedUpdate.intern() {
+            app.add_plugins(InterpolationPlugin);
+        }
+    }
+}
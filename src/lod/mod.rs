@@ -0,0 +1,223 @@
+use std::f32::consts::PI;
+use bevy::{ecs::schedule::{InternedScheduleLabel, ScheduleLabel}, prelude::*};
+
+use crate::{health::Dead, inspect::ReadOnly, leg::{IKLeg, LegCreature, LegOf, LegSide}, schedule::{configure_sets, LocomotionSet}};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Reflect)]
+pub enum LodTier {
+    #[default] Full,
+    Reduced,
+    Minimal,
+    Baked,
+}
+
+impl LodTier {
+    const ALL: [LodTier; 4] = [LodTier::Full, LodTier::Reduced, LodTier::Minimal, LodTier::Baked];
+
+    // How many frames pass between two IK solves / ground queries.
+    pub fn update_interval(self) -> u32 {
+        match self {
+            LodTier::Full => 1,
+            LodTier::Reduced => 2,
+            LodTier::Minimal => 4,
+            LodTier::Baked => 8,
+        }
+    }
+
+    // Ground probe rays each leg may cast per update, the foothold search spreads what it can afford.
+    pub fn ray_count(self) -> usize {
+        match self {
+            LodTier::Full => usize::MAX,
+            LodTier::Reduced => 4,
+            LodTier::Minimal => 1,
+            LodTier::Baked => 0,
+        }
+    }
+
+    // Iterations the chain solver gets for legs with more than two bones, far legs settle for a rougher fit.
+    pub fn ik_iterations(self) -> usize {
+        match self {
+            LodTier::Full => 30,
+            LodTier::Reduced => 15,
+            LodTier::Minimal => 8,
+            LodTier::Baked => 4,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct LodSettings {
+    // Camera distances at which a creature drops to Reduced, Minimal and Baked.
+    pub distances: [f32; 3],
+    pub hysteresis: f32,
+    pub blend_duration: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self { distances: [10., 25., 50.], hysteresis: 1., blend_duration: 0.5 }
+    }
+}
+
+// Put on a creature to opt into LOD. Its legs are given one too, and the creature's state is mirrored onto them every frame.
+#[derive(Component, Clone, Default, Reflect)]
+#[reflect(Component)]
+pub struct LocomotionLod {
+    pub tier: LodTier,
+    #[reflect(@ReadOnly)]
+    baked_blend: f32,
+    #[reflect(@ReadOnly)]
+    baked_phase: f32,
+    // How fast the body moves while fully baked, the pace at which the baked cycle moves the feet.
+    #[reflect(@ReadOnly)]
+    baked_speed: f32,
+    #[reflect(@ReadOnly)]
+    update_frame: bool,
+    // Ticks this creature has counted, seeded from its entity the first time so a swarm on the same tier
+    // doesn't all update on the same tick. Kept on the creature so a restored scene keeps its stagger.
+    #[reflect(@ReadOnly)]
+    stagger: Option<u32>,
+}
+
+impl LocomotionLod {
+    pub fn should_update(&self) -> bool {
+        self.update_frame
+    }
+
+    pub fn draws_debug(&self) -> bool {
+        self.tier == LodTier::Full
+    }
+
+    pub fn baked_blend(&self) -> f32 {
+        self.baked_blend
+    }
+
+    pub fn baked_phase(&self) -> f32 {
+        self.baked_phase
+    }
+
+    pub fn baked_speed(&self) -> f32 {
+        self.baked_speed
+    }
+}
+
+pub struct LodPlugin {
+    schedule: InternedScheduleLabel,
+}
+
+impl LodPlugin {
+    pub fn new() -> Self {
+        Self { schedule: FixedUpdate.intern() }
+    }
+
+    // Runs the systems in another schedule, e.g. FixedUpdate or PostUpdate. See LocomotionSet.
+    pub fn in_schedule(mut self, schedule: impl ScheduleLabel) -> Self {
+        self.schedule = schedule.intern();
+        self
+    }
+}
+
+impl Default for LodPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        configure_sets(app, self.schedule);
+        app.register_type::<LocomotionLod>()
+        .init_resource::<LodSettings>()
+        .add_systems(self.schedule, (update_lod_tiers, propagate_lod_to_legs).chain().in_set(LocomotionSet::Input));
+    }
+}
+
+fn update_lod_tiers(
+    mut creature_query: Query<(Entity, &GlobalTransform, &LegCreature, &mut LocomotionLod, Has<Dead>)>,
+    leg_query: Query<&IKLeg>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    settings: Res<LodSettings>,
+    time: Res<Time>,
+) {
+    for (creature_entity, transform, creature, mut lod, dead) in creature_query.iter_mut() {
+        let distance = camera_query.iter()
+            .map(|camera| camera.translation().distance(transform.translation()))
+            .reduce(f32::min)
+            .unwrap_or(0.);
+        lod.tier = tier_for_distance(lod.tier, distance, &settings);
+        // Counts ticks of whatever schedule this runs in, unlike FrameCount, so staggering doesn't depend on frame rate.
+        let tick = *lod.stagger.get_or_insert(creature_entity.index());
+        lod.update_frame = tick % lod.tier.update_interval() == 0;
+        lod.stagger = Some(tick.wrapping_add(1));
+
+        let blend_target = if lod.tier == LodTier::Baked { 1. } else { 0. };
+        let blend_step = time.delta_seconds() / settings.blend_duration.max(f32::EPSILON);
+        lod.baked_blend = if lod.baked_blend < blend_target {
+            (lod.baked_blend + blend_step).min(blend_target)
+        } else {
+            (lod.baked_blend - blend_step).max(blend_target)
+        };
+
+        lod.baked_speed = 0.;
+        // Dead creatures left standing stay where they are whatever they were steered towards.
+        if creature.is_moving() && !dead {
+            let (step_distance, step_duration) = creature.legs_info.iter()
+                .find_map(|(leg_entity, _)| leg_query.get(*leg_entity).ok())
+                .map_or((0.1, 0.15), |leg| (leg.step_distance, leg.step_duration));
+            lod.baked_phase = (lod.baked_phase + time.delta_seconds() / (2. * step_duration)).fract();
+            // A planted foot slides back two step distances over one step duration, see baked_step_target.
+            lod.baked_speed = 2. * step_distance / step_duration;
+        }
+    }
+}
+
+fn propagate_lod_to_legs(
+    mut commands: Commands,
+    creature_query: Query<&LocomotionLod, With<LegCreature>>,
+    mut leg_query: Query<(Entity, &LegOf, Option<&mut LocomotionLod>), Without<LegCreature>>,
+) {
+    for (leg_entity, leg_of, leg_lod) in leg_query.iter_mut() {
+        let Ok(creature_lod) = creature_query.get(leg_of.creature) else {continue;};
+        let Some(mut leg_lod) = leg_lod else {
+            commands.entity(leg_entity).insert(creature_lod.clone());
+            continue;
+        };
+        leg_lod.tier = creature_lod.tier;
+        leg_lod.update_frame = creature_lod.update_frame;
+        leg_lod.baked_blend = creature_lod.baked_blend;
+        leg_lod.baked_phase = creature_lod.baked_phase;
+    }
+}
+
+fn tier_for_distance(current: LodTier, distance: f32, settings: &LodSettings) -> LodTier {
+    let mut tier = LodTier::Full;
+    for (i, threshold) in settings.distances.iter().enumerate() {
+        let next = LodTier::ALL[i + 1];
+        // Hysteresis keeps creatures sitting right on a threshold from flickering between tiers.
+        let threshold = if current >= next { threshold - settings.hysteresis } else { threshold + settings.hysteresis };
+        if distance > threshold {
+            tier = next;
+        }
+    }
+    tier
+}
+
+// Cheap walk cycle used at the far tier: the foot slides back along the ground during stance and arcs forward
+// during swing, with the two sides half a cycle apart.
+pub fn baked_step_target(
+    nominal: Vec3,
+    up: Vec3,
+    move_dir: Vec3,
+    leg: &IKLeg,
+    phase: f32,
+) -> Vec3 {
+    let phase = if leg.leg_side == LegSide::Right { (phase + 0.5).fract() } else { phase };
+    let (along, lift) = if phase < 0.5 {
+        let swing = phase * 2.;
+        (swing * 2. - 1., (swing * PI).sin())
+    } else {
+        let stance = (phase - 0.5) * 2.;
+        (1. - stance * 2., 0.)
+    };
+    nominal + move_dir * leg.step_distance * along + up * leg.step_height * lift
+}
@@ -0,0 +1,278 @@
+use std::marker::PhantomData;
+use bevy::{ecs::{entity::{EntityHashMap, EntityHashSet, MapEntities}, reflect::ReflectMapEntities, system::{StaticSystemParam, SystemParam}}, prelude::*};
+
+use crate::{ground::{DefaultGround, GroundQuery}, inspect::ReadOnly, leg::LegCreature};
+
+#[derive(Component, Reflect)]
+#[reflect(Component, MapEntities)]
+pub struct Perception {
+    pub view_distance: f32,
+    pub fov: f32,
+    pub eye_offset: Vec3,
+    pub look_direction: Vec3,
+    pub hearing_radius: f32,
+    pub update_interval: f32,
+    #[reflect(@ReadOnly)]
+    since_update: f32,
+    #[reflect(@ReadOnly)]
+    seen: EntityHashSet,
+    #[reflect(@ReadOnly)]
+    heard: EntityHashSet,
+    #[reflect(@ReadOnly)]
+    last_known: EntityHashMap<Vec3>,
+}
+
+impl Perception {
+    pub fn new(
+        view_distance: f32,
+        fov: f32,
+        eye_offset: Vec3,
+        hearing_radius: f32,
+    ) -> Self {
+        Self {
+            view_distance,
+            fov,
+            eye_offset,
+            look_direction: Vec3::Z,
+            hearing_radius,
+            update_interval: 0.2,
+            since_update: f32::INFINITY,
+            seen: EntityHashSet::default(),
+            heard: EntityHashSet::default(),
+            last_known: EntityHashMap::default(),
+        }
+    }
+
+    pub fn sees(&self, target: Entity) -> bool {
+        self.seen.contains(&target)
+    }
+
+    pub fn hears(&self, target: Entity) -> bool {
+        self.heard.contains(&target)
+    }
+
+    pub fn last_known_position(&self, target: Entity) -> Option<Vec3> {
+        self.last_known.get(&target).copied()
+    }
+}
+
+// Points what the creature remembers at the respawned targets when it's loaded from a scene.
+impl MapEntities for Perception {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for set in [&mut self.seen, &mut self.heard] {
+            *set = set.drain().map(|target| entity_mapper.map_entity(target)).collect();
+        }
+        self.last_known = self.last_known.drain().map(|(target, position)| (entity_mapper.map_entity(target), position)).collect();
+    }
+}
+
+// Anything that can be seen or heard. Loudness scales the observer's hearing radius, 0 is silent.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Perceivable {
+    pub loudness: f32,
+}
+
+impl Default for Perceivable {
+    fn default() -> Self {
+        Self { loudness: 1. }
+    }
+}
+
+// Caps the line-of-sight rays cast per frame across every observer, observers that don't fit wait for the next frame.
+#[derive(Resource)]
+pub struct PerceptionSettings {
+    pub max_rays_per_frame: usize,
+}
+
+impl Default for PerceptionSettings {
+    fn default() -> Self {
+        Self { max_rays_per_frame: 64 }
+    }
+}
+
+#[derive(Event)]
+pub struct TargetSeen {
+    pub observer: Entity,
+    pub target: Entity,
+    pub position: Vec3,
+}
+
+#[derive(Event)]
+pub struct TargetLost {
+    pub observer: Entity,
+    pub target: Entity,
+    pub last_known_position: Vec3,
+}
+
+#[derive(Event)]
+pub struct TargetHeard {
+    pub observer: Entity,
+    pub target: Entity,
+    pub position: Vec3,
+}
+
+// Occlusion goes through the same ground query backend as the legs.
+pub struct PerceptionPlugin<G = DefaultGround> {
+    marker: PhantomData<fn() -> G>,
+}
+
+impl PerceptionPlugin {
+    pub fn new() -> Self {
+        Self::with_ground()
+    }
+}
+
+impl Default for PerceptionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> PerceptionPlugin<G> {
+    pub fn with_ground() -> Self {
+        Self { marker: PhantomData }
+    }
+}
+
+impl<G: SystemParam + 'static> Plugin for PerceptionPlugin<G>
+where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    fn build(&self, app: &mut App) {
+        app.register_type::<Perception>()
+        .register_type::<Perceivable>()
+        // Reflected as an opaque value, so scenes need to be told how to save the seen and heard sets.
+        .register_type::<EntityHashSet>()
+        .register_type_data::<EntityHashSet, ReflectSerialize>()
+        .register_type_data::<EntityHashSet, ReflectDeserialize>()
+        .init_resource::<PerceptionSettings>()
+        .add_event::<TargetSeen>()
+        .add_event::<TargetLost>()
+        .add_event::<TargetHeard>()
+        .add_systems(Update, update_perception::<G>);
+    }
+}
+
+// What observers can perceive, and the hierarchy that tells the parts of things apart.
+#[derive(SystemParam)]
+struct Perceivables<'w, 's> {
+    targets: Query<'w, 's, (Entity, &'static GlobalTransform, &'static Perceivable)>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+#[derive(SystemParam)]
+struct PerceptionEvents<'w> {
+    seen: EventWriter<'w, TargetSeen>,
+    lost: EventWriter<'w, TargetLost>,
+    heard: EventWriter<'w, TargetHeard>,
+}
+
+fn update_perception<G: SystemParam>(
+    mut observer_query: Query<(Entity, &GlobalTransform, &mut Perception, Option<&LegCreature>)>,
+    perceivables: Perceivables,
+    settings: Res<PerceptionSettings>,
+    mut ground: StaticSystemParam<G>,
+    time: Res<Time>,
+    mut events: PerceptionEvents,
+    mut due: Local<Vec<(Entity, f32)>>,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    due.clear();
+    for (observer_entity, _, mut perception, _) in observer_query.iter_mut() {
+        perception.since_update += time.delta_seconds();
+        if perception.since_update >= perception.update_interval {
+            due.push((observer_entity, perception.since_update));
+        }
+    }
+    // Most overdue first, so a tight ray budget still gets around to everyone.
+    due.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut rays_left = settings.max_rays_per_frame;
+    for &(observer_entity, _) in due.iter() {
+        let Ok((_, observer_transform, mut perception, creature)) = observer_query.get_mut(observer_entity) else {continue;};
+        let perception = &mut *perception;
+        let eye = observer_transform.transform_point(perception.eye_offset);
+        let look = observer_transform.affine().transform_vector3(perception.look_direction).normalize_or_zero();
+        let rays_needed = perceivables.targets.iter()
+            .filter(|(target, transform, _)| *target != observer_entity && in_view_cone(perception, eye, look, transform.translation()))
+            .count();
+        if rays_needed > rays_left && rays_left < settings.max_rays_per_frame {
+            break;
+        }
+        rays_left = rays_left.saturating_sub(rays_needed);
+        perception.since_update = 0.;
+
+        let legs = creature.map_or(&[][..], |creature| creature.legs_info.as_slice());
+        let filter = |entity: Entity| {
+            !is_part_of(entity, observer_entity, &perceivables.parents) && !legs.iter().any(|(leg, _)| is_part_of(entity, *leg, &perceivables.parents))
+        };
+        for (target, target_transform, perceivable) in perceivables.targets.iter() {
+            if target == observer_entity {
+                continue;
+            }
+            let position = target_transform.translation();
+            let visible = in_view_cone(perception, eye, look, position)
+                && line_of_sight(&mut *ground, eye, position, target, &filter, &perceivables.parents);
+            let audible = perceivable.loudness > 0. && eye.distance(position) <= perception.hearing_radius * perceivable.loudness;
+            if visible || audible {
+                perception.last_known.insert(target, position);
+            }
+            if visible {
+                if perception.seen.insert(target) {
+                    events.seen.send(TargetSeen { observer: observer_entity, target, position });
+                }
+            } else if perception.seen.remove(&target) {
+                events.lost.send(TargetLost { observer: observer_entity, target, last_known_position: perception.last_known[&target] });
+            }
+            if audible {
+                if perception.heard.insert(target) {
+                    events.heard.send(TargetHeard { observer: observer_entity, target, position });
+                }
+            } else {
+                perception.heard.remove(&target);
+            }
+        }
+        // Despawned targets, or ones that stopped being perceivable, count as lost where they were last seen.
+        perception.seen.retain(|target| {
+            if perceivables.targets.contains(*target) {
+                return true;
+            }
+            if let Some(last_known_position) = perception.last_known.remove(target) {
+                events.lost.send(TargetLost { observer: observer_entity, target: *target, last_known_position });
+            }
+            false
+        });
+        perception.heard.retain(|target| perceivables.targets.contains(*target));
+        perception.last_known.retain(|target, _| perceivables.targets.contains(*target));
+    }
+}
+
+fn in_view_cone(perception: &Perception, eye: Vec3, look: Vec3, position: Vec3) -> bool {
+    let to_target = position - eye;
+    if to_target.length() > perception.view_distance {
+        return false;
+    }
+    to_target.length() < f32::EPSILON || look.angle_between(to_target) <= perception.fov / 2.
+}
+
+fn line_of_sight(
+    ground: &mut impl GroundQuery,
+    eye: Vec3,
+    position: Vec3,
+    target: Entity,
+    filter: &dyn Fn(Entity) -> bool,
+    parent_query: &Query<&Parent>,
+) -> bool {
+    let Ok(direction) = Dir3::new(position - eye) else {return true;};
+    let distance = eye.distance(position);
+    match ground.cast_ground_ray(Ray3d { origin: eye, direction }, distance, filter) {
+        Some(hit) => hit.distance >= distance - 0.05 || hit.entity.is_some_and(|entity| is_part_of(entity, target, parent_query)),
+        None => true,
+    }
+}
+
+fn is_part_of(entity: Entity, root: Entity, parent_query: &Query<&Parent>) -> bool {
+    entity == root || parent_query.iter_ancestors(entity).any(|ancestor| ancestor == root)
+}
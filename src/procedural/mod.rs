@@ -0,0 +1,241 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use bevy::{ecs::{entity::MapEntities, reflect::ReflectMapEntities}, prelude::*, render::{mesh::{skinning::{SkinnedMesh, SkinnedMeshInverseBindposes}, Indices, PrimitiveTopology, VertexAttributeValues}, render_asset::RenderAssetUsages}};
+
+// Legs built in code instead of loaded from leg/leg.glb: a tapered tube over any number of bones with a ball on each
+// joint, skinned to a joint at the hip, one between each pair of bones and one at the foot. The arm solver finds them
+// the same way it finds the glTF rig, and measures every bone from the joints, so they needn't be the same length.
+// Two bones are solved analytically, more with ik::solve_chain.
+#[derive(Clone, Debug)]
+pub struct LegShape {
+    // Length of each bone, from the hip down to the foot. At least one, and every one longer than zero.
+    pub lengths: Vec<f32>,
+    // Radius of the leg at the hip, it narrows down to `radius * taper` at the foot.
+    pub radius: f32,
+    pub taper: f32,
+    // Radius of the balls on the hip and the joints between the bones.
+    pub joint_radius: f32,
+    // Faces around the leg.
+    pub sides: u32,
+    // Rings along each bone, more bend more smoothly at the joints.
+    pub rings_per_bone: u32,
+}
+
+impl Default for LegShape {
+    fn default() -> Self {
+        Self { lengths: vec![0.35, 0.35], radius: 0.03, taper: 0.4, joint_radius: 0.04, sides: 8, rings_per_bone: 4 }
+    }
+}
+
+impl LegShape {
+    // Panics on a shape with no bones or with bones that have no length, there'd be no leg to build or to solve.
+    fn validate(&self) {
+        assert!(!self.lengths.is_empty(), "a leg shape needs at least one bone");
+        assert!(self.lengths.iter().all(|length| *length > 0.), "leg bones need to be longer than zero, got {:?}", self.lengths);
+    }
+
+    pub fn reach(&self) -> f32 {
+        self.lengths.iter().sum()
+    }
+
+    // How far up the leg each joint is in the bind pose, from the hip at 0 to the foot.
+    pub fn joint_heights(&self) -> Vec<f32> {
+        let mut height = 0.;
+        let mut heights = vec![0.];
+        for length in &self.lengths {
+            height += length;
+            heights.push(height);
+        }
+        heights
+    }
+
+    fn radius_at(&self, height: f32) -> f32 {
+        self.radius * (1. + (self.taper - 1.) * height / self.reach())
+    }
+
+    // The two joints a point `height` up the leg follows and how much, blended across the ball of the joint between
+    // the bones nearest to it.
+    fn skin_weights(&self, joint_heights: &[f32], height: f32) -> ([u16; 4], [f32; 4]) {
+        let inner = 1..joint_heights.len().saturating_sub(1);
+        let Some(joint) = inner.min_by(|a, b| (joint_heights[*a] - height).abs().total_cmp(&(joint_heights[*b] - height).abs())) else {
+            return ([0; 4], [1., 0., 0., 0.]);
+        };
+        let t = ((height - joint_heights[joint] + self.joint_radius) / (self.joint_radius * 2.).max(f32::EPSILON)).clamp(0., 1.);
+        let weight = t * t * (3. - 2. * t);
+        ([joint as u16 - 1, joint as u16, 0, 0], [1. - weight, weight, 0., 0.])
+    }
+}
+
+#[derive(Default)]
+struct SkinBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    joint_indices: Vec<[u16; 4]>,
+    joint_weights: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl SkinBuilder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: Vec2, (joints, weights): ([u16; 4], [f32; 4])) {
+        self.positions.push(position.into());
+        self.normals.push(normal.into());
+        self.uvs.push(uv.into());
+        self.joint_indices.push(joints);
+        self.joint_weights.push(weights);
+    }
+
+    // Stitches the last `rows` rings of `sides + 1` vertices, going up, the last vertex of a ring repeats the first for the UV seam.
+    fn stitch(&mut self, rows: u32, sides: u32) {
+        let first = self.positions.len() as u32 - rows * (sides + 1);
+        for row in 0..rows - 1 {
+            for side in 0..sides {
+                let a = first + row * (sides + 1) + side;
+                let b = a + sides + 1;
+                self.indices.extend([a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+    }
+
+    // A ball following `joint` alone.
+    fn sphere(&mut self, center: Vec3, radius: f32, sides: u32, joint: u16) {
+        let rows = sides / 2 + 1;
+        for row in 0..rows {
+            let latitude = -FRAC_PI_2 + PI * row as f32 / (rows - 1) as f32;
+            for side in 0..=sides {
+                let (sin, cos) = (TAU * side as f32 / sides as f32).sin_cos();
+                let normal = Vec3::new(cos * latitude.cos(), latitude.sin(), sin * latitude.cos());
+                self.vertex(center + normal * radius, normal, Vec2::new(side as f32 / sides as f32, row as f32 / (rows - 1) as f32), ([joint, 0, 0, 0], [1., 0., 0., 0.]));
+            }
+        }
+        self.stitch(rows, sides);
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_JOINT_INDEX, VertexAttributeValues::Uint16x4(self.joint_indices))
+            .with_inserted_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, self.joint_weights)
+            .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
+// The leg in its bind pose, standing up along +Y from the hip at the origin.
+pub fn leg_mesh(shape: &LegShape) -> Mesh {
+    shape.validate();
+    let sides = shape.sides.max(3);
+    let rings = shape.rings_per_bone.max(1);
+    let joint_heights = shape.joint_heights();
+    let heights: Vec<f32> = std::iter::once(0.)
+        .chain(joint_heights.windows(2).flat_map(|bone| (1..=rings).map(move |i| bone[0] + (bone[1] - bone[0]) * i as f32 / rings as f32)))
+        .collect();
+    // The tube is a cone, its normals lean up as it narrows.
+    let slope = (shape.radius_at(shape.reach()) - shape.radius) / shape.reach();
+
+    let mut skin = SkinBuilder::default();
+    for height in &heights {
+        let radius = shape.radius_at(*height);
+        for side in 0..=sides {
+            let (sin, cos) = (TAU * side as f32 / sides as f32).sin_cos();
+            let uv = Vec2::new(side as f32 / sides as f32, height / shape.reach());
+            skin.vertex(Vec3::new(cos * radius, *height, sin * radius), Vec3::new(cos, -slope, sin).normalize(), uv, shape.skin_weights(&joint_heights, *height));
+        }
+    }
+    skin.stitch(heights.len() as u32, sides);
+    // The hip and the joints between the bones, the foot has none of its own.
+    let last_bone = joint_heights.len().saturating_sub(2);
+    for (joint, height) in joint_heights[..=last_bone].iter().enumerate() {
+        skin.sphere(Vec3::Y * *height, shape.joint_radius, sides, joint as u16);
+    }
+    // Closes off the foot.
+    skin.sphere(Vec3::Y * shape.reach(), shape.radius_at(shape.reach()), sides, last_bone as u16);
+    skin.build()
+}
+
+// One per joint of spawn_leg_skeleton, taking the bind pose back to each joint.
+pub fn leg_inverse_bindposes(shape: &LegShape) -> SkinnedMeshInverseBindposes {
+    shape.validate();
+    shape.joint_heights().into_iter().map(|height| Mat4::from_translation(-Vec3::Y * height)).collect::<Vec<_>>().into()
+}
+
+// Everything legs of one shape share, made once and handed to spawn_leg_rig for each leg.
+#[derive(Clone)]
+pub struct LegAssets {
+    pub shape: LegShape,
+    pub mesh: Handle<Mesh>,
+    pub inverse_bindposes: Handle<SkinnedMeshInverseBindposes>,
+    pub material: Handle<StandardMaterial>,
+}
+
+impl LegAssets {
+    pub fn new(
+        shape: LegShape,
+        meshes: &mut Assets<Mesh>,
+        inverse_bindposes: &mut Assets<SkinnedMeshInverseBindposes>,
+        material: Handle<StandardMaterial>,
+    ) -> Self {
+        let mesh = meshes.add(leg_mesh(&shape));
+        let inverse_bindposes = inverse_bindposes.add(leg_inverse_bindposes(&shape));
+        Self { shape, mesh, inverse_bindposes, material }
+    }
+}
+
+// The joints a procedural leg's skin is bound to. Saved in scenes in place of SkinnedMesh, Bevy can't serialize
+// the handles of a loaded mesh, LegAssets::restore_skin skins the leg again once it's back.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component, MapEntities)]
+pub struct LegSkin {
+    pub joints: Vec<Entity>,
+}
+
+impl MapEntities for LegSkin {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for joint in &mut self.joints {
+            *joint = entity_mapper.map_entity(*joint);
+        }
+    }
+}
+
+impl LegAssets {
+    // Puts the mesh back on a leg restored from a scene, under `leg`. Legs that still have theirs are left alone.
+    pub fn restore_skin(&self, world: &mut World, leg: Entity) {
+        let mut stack = vec![leg];
+        while let Some(entity) = stack.pop() {
+            if let Some(children) = world.get::<Children>(entity) {
+                stack.extend(children.iter());
+            }
+            let Some(skin) = world.get::<LegSkin>(entity) else {continue;};
+            if world.get::<SkinnedMesh>(entity).is_some() {
+                continue;
+            }
+            let skinned_mesh = SkinnedMesh { inverse_bindposes: self.inverse_bindposes.clone(), joints: skin.joints.clone() };
+            world.entity_mut(entity).insert((self.mesh.clone(), self.material.clone(), VisibilityBundle::default(), skinned_mesh));
+        }
+    }
+}
+
+// The hip, a joint at the end of each bone down to the foot, each a child of the one before. Spawn the hip under
+// the leg root.
+pub fn spawn_leg_skeleton(commands: &mut Commands, shape: &LegShape) -> Vec<Entity> {
+    shape.validate();
+    let mut joints = vec![commands.spawn(SpatialBundle::default()).id()];
+    for length in &shape.lengths {
+        let joint = commands.spawn(SpatialBundle::from_transform(Transform::from_xyz(0., *length, 0.))).id();
+        commands.entity(*joints.last().unwrap()).add_child(joint);
+        joints.push(joint);
+    }
+    joints
+}
+
+// What instancing leg.glb gives: a root with the skeleton and the skinned mesh under it. Add IKArm and IKLeg to
+// the returned root to make it a leg.
+pub fn spawn_leg_rig(commands: &mut Commands, assets: &LegAssets, transform: Transform) -> Entity {
+    let joints = spawn_leg_skeleton(commands, &assets.shape);
+    let skin = commands.spawn((
+        PbrBundle { mesh: assets.mesh.clone(), material: assets.material.clone(), ..default() },
+        SkinnedMesh { inverse_bindposes: assets.inverse_bindposes.clone(), joints: joints.clone() },
+        LegSkin { joints: joints.clone() },
+    )).id();
+    commands.spawn(SpatialBundle::from_transform(transform)).push_children(&[joints[0], skin]).id()
+}
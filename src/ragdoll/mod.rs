@@ -0,0 +1,92 @@
+#[cfg(feature = "ragdoll")]
+use avian3d::prelude::{Collider, RigidBody};
+use bevy::{ecs::system::{StaticSystemParam, SystemParam}, prelude::*};
+
+use crate::{dismember::GRAVITY, foothold::SurfaceQuery, ground::GroundQuery, health::{Dead, DeathPose}, inspect::ReadOnly, leg::LegCreature, IKArm::IKArm};
+
+// A dead creature going down. The leg systems skip it, its body falls onto the ground and its legs curl up under it.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Crumple {
+    #[reflect(@ReadOnly)]
+    velocity: Vec3,
+    #[reflect(@ReadOnly)]
+    rest_height: f32,
+    #[reflect(@ReadOnly)]
+    grounded: bool,
+    // The physics engine moves the body, only the legs are posed here.
+    #[reflect(@ReadOnly)]
+    physics: bool,
+}
+
+impl Crumple {
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+}
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct CurledLeg;
+
+// Creatures loaded from a scene come with the Crumple they were saved with.
+type JustDied = (Added<Dead>, Without<Crumple>);
+
+pub(crate) fn start_crumple(
+    mut commands: Commands,
+    dead_query: Query<(Entity, &Dead, &LegCreature), JustDied>,
+) {
+    for (creature_entity, dead, leg_creature) in dead_query.iter() {
+        let physics = match dead.pose {
+            DeathPose::Crumple => false,
+            #[cfg(feature = "ragdoll")]
+            DeathPose::Ragdoll => true,
+            _ => continue,
+        };
+        commands.entity(creature_entity).insert(Crumple { velocity: Vec3::ZERO, rest_height: dead.rest_height, grounded: false, physics });
+        #[cfg(feature = "ragdoll")]
+        if physics {
+            commands.entity(creature_entity).insert((RigidBody::Dynamic, Collider::sphere(dead.rest_height)));
+        }
+        for (leg_entity, _) in &leg_creature.legs_info {
+            let Some(mut leg) = commands.get_entity(*leg_entity) else {continue;};
+            leg.insert(CurledLeg);
+        }
+    }
+}
+
+// Disjoint from the crumpling bodies so both can move.
+type CurledLegs = (With<CurledLeg>, Without<Crumple>);
+
+pub(crate) fn crumple_creatures<G: SystemParam>(
+    mut creature_query: Query<(&mut Transform, &LegCreature, &mut Crumple)>,
+    mut leg_query: Query<(&mut Transform, &mut IKArm), CurledLegs>,
+    mut ground: StaticSystemParam<G>,
+    surfaces: SurfaceQuery,
+    time: Res<Time>,
+) where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let delta = time.delta_seconds();
+    for (mut transform, leg_creature, mut crumple) in creature_query.iter_mut() {
+        if !crumple.physics && !crumple.grounded {
+            crumple.velocity += Vec3::NEG_Y * GRAVITY * delta;
+            let motion = crumple.velocity * delta;
+            // Looks far enough down to cover this frame's fall and the height the body rests at.
+            let ray = Ray3d { origin: transform.translation, direction: Dir3::NEG_Y };
+            match ground.cast_ground_ray(ray, motion.length() + crumple.rest_height, &|entity| !surfaces.is_creature_part(entity)) {
+                Some(hit) => {
+                    transform.translation = hit.position + Vec3::Y * crumple.rest_height;
+                    crumple.grounded = true;
+                }
+                None => transform.translation += motion,
+            }
+        }
+        for (leg_entity, leg_offset) in &leg_creature.legs_info {
+            let Ok((mut leg_transform, mut arm)) = leg_query.get_mut(*leg_entity) else {continue;};
+            leg_transform.translation = transform.translation + *leg_offset;
+            let curled = transform.translation + *leg_offset * 0.4 - Vec3::Y * crumple.rest_height;
+            arm.target = arm.target.lerp(curled, 0.1);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use bevy::{ecs::schedule::{InternedScheduleLabel, ScheduleLabel}, prelude::*, transform::TransformSystem};
+
+// The locomotion pipeline, in the order it runs every tick: Input -> GaitPlanning -> FootPlacement -> BodyPose -> IkSolve.
+// The plugins run it in FixedUpdate unless told otherwise with `in_schedule`, so the same inputs tick for tick give
+// bit-identical gaits at any frame rate. In Update or FixedUpdate transforms propagate afterwards in PostUpdate; in
+// PostUpdate the sets run before TransformPropagate, so solved joints show the same frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LocomotionSet {
+    // Steering input, LOD tiers and arms whose rig just came in.
+    Input,
+    // Leg bookkeeping, which gait group may step, and obstacles ahead.
+    GaitPlanning,
+    // Foothold search and stepping, plus attacks and grabs steering their arms.
+    FootPlacement,
+    // Body height and orientation from the new foot positions, jumping, falling and dying.
+    BodyPose,
+    // Arms following entities, then the IK solve itself.
+    IkSolve,
+}
+
+// Every locomotion plugin calls this for its schedule, configuring the same sets twice is harmless.
+pub(crate) fn configure_sets(app: &mut App, schedule: InternedScheduleLabel) {
+    app.configure_sets(schedule, (
+        LocomotionSet::Input,
+        LocomotionSet::GaitPlanning,
+        LocomotionSet::FootPlacement,
+        LocomotionSet::BodyPose,
+        LocomotionSet::IkSolve,
+    ).chain());
+    if schedule == PostUpdate.intern() {
+        app.configure_sets(schedule, LocomotionSet::IkSolve.before(TransformSystem::TransformPropagate));
+    }
+}
@@ -1,42 +1,62 @@
 use bevy::{color::palettes::css::BLACK, math::{NormedVectorSpace, VectorSpace}, prelude::*, reflect::Array, render::mesh::{self, skinning::SkinnedMesh}};
 
-use crate::{leg::{IKLeg, LegCreature, LegSide}, IKArm::{self, IKArmTarget}, Movable};
+use crate::{health::Health, leg::{IKLeg, LegCreature, LegSide}, lod::LocomotionLod, perception::Perception, procedural::{spawn_leg_rig, LegAssets}, IKArm::{self, IKArmTarget}};
 
+// Spawns a four-legged spider body at `position` with its legs, and returns the body.
 pub fn spawn_spider(
-    mut commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    mut meshes: &mut ResMut<Assets<Mesh>>,
-    mut materials: &mut ResMut<Assets<StandardMaterial>>
-) {
-    let target = commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Cuboid::new(0.1, 0.1, 0.1)),
-            material: materials.add(Color::srgb_u8(124, 144, 255)),
-            transform: Transform::from_xyz(-0.5, 0., 0.),
-            ..default()
-        },
-        Movable,
-    )).id();
-
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    position: Vec3,
+) -> Entity {
     //spawn_test_arm(&mut commands, &asset_server, target);
 
-    let legs_info: Vec<(Entity, Vec3)> = spawn_legs(&mut commands, &asset_server);
+    let legs_info: Vec<(Entity, Vec3)> = spawn_legs(commands, position, |commands, transform| commands.spawn(SceneBundle {
+        scene: asset_server
+            .load(GltfAssetLabel::Scene(0).from_asset("leg/leg.glb")),
+        transform,
+        ..default()
+    }).id());
+    spawn_body(commands, meshes, materials, position, legs_info)
+}
+
+// The same spider with legs built from `legs` instead of leg.glb, see the procedural module.
+pub fn spawn_procedural_spider(
+    commands: &mut Commands,
+    legs: &LegAssets,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    position: Vec3,
+) -> Entity {
+    let legs_info = spawn_legs(commands, position, |commands, transform| spawn_leg_rig(commands, legs, transform));
+    spawn_body(commands, meshes, materials, position, legs_info)
+}
 
+fn spawn_body(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    position: Vec3,
+    legs_info: Vec<(Entity, Vec3)>,
+) -> Entity {
     commands.spawn((
         PbrBundle {
             mesh: meshes.add(Cuboid::new(0.3, 0.3, 0.3)),
-            transform: Transform::from_xyz(0., 0.3, 0.0),
+            transform: Transform::from_translation(position),
             material: materials.add(Color::srgb_u8(10, 10, 10)),
             ..default()
         },
-        //Movable,
-        LegCreature::new(LegSide::None, 0.2, legs_info)
-    ));
+        LegCreature::new(LegSide::None, 0.2, legs_info),
+        Health::new(100.),
+        Perception::new(8., 120_f32.to_radians(), Vec3::new(0., 0.1, 0.15), 3.),
+        LocomotionLod::default(),
+    )).id()
 }
 
 fn spawn_test_arm(
-    mut commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+    commands: &mut Commands,
+    asset_server: &AssetServer,
     target: Entity,
 ) {
     commands.spawn((SceneBundle {
@@ -44,19 +64,18 @@ fn spawn_test_arm(
             .load(GltfAssetLabel::Scene(0).from_asset("leg/leg.glb")),
         ..default()
         }, 
-        IKArm::IKArm { 
-            target: Vec3{x: 1., y: 0., z: 1.},
-            up: Vec3::Y
-        },
+        IKArm::IKArm::new(Vec3{x: 1., y: 0., z: 1.}, Vec3::Y),
         Name::new("Arm"),
         IKArmTarget {target}
     )
     );
 }
 
+// `spawn_rig` spawns the root of a leg with its skinned mesh at the given transform.
 fn spawn_legs(
-    mut commands: &mut Commands,
-    asset_server: &Res<AssetServer>
+    commands: &mut Commands,
+    position: Vec3,
+    mut spawn_rig: impl FnMut(&mut Commands, Transform) -> Entity,
 ) -> Vec<(Entity, Vec3)> {
     let mut left_legs = Vec::new();
     let mut right_legs = Vec::new();
@@ -70,16 +89,9 @@ fn spawn_legs(
             let side3 = if j == 0 { side } else {side2};
             let collector = if (i == 0) { &mut left_legs } else {&mut right_legs };
             let name = format!("{i}{j}", i=i, j=j);
-            println!("Name: {}", name);
-            collector.push((commands.spawn((SceneBundle {
-                scene: asset_server
-                    .load(GltfAssetLabel::Scene(0).from_asset("leg/leg.glb")),
-                ..default()
-                }, 
-                IKArm::IKArm { 
-                    target: Vec3{x: 1., y: 0., z: 1.},
-                    up: Vec3::Y
-                },
+            let leg = spawn_rig(commands, Transform::from_translation(position + offset));
+            collector.push((commands.entity(leg).insert((
+                IKArm::IKArm::new(Vec3{x: 1., y: 0., z: 1.}, Vec3::Y),
                 IKLeg::new(
                     Vec3{x: 0.5 * side_mult, y: -0.1, z: 0.35 * front_or_back_mult }, 
                     0.1, 
@@ -88,6 +100,8 @@ fn spawn_legs(
                     side3,
                     false,
                 ),
+                LocomotionLod::default(),
+                Health::new(30.),
                 Name::new(name)
             )
             ).id(), offset));
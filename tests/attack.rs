@@ -0,0 +1,80 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    attack::{Attack, AttackHit},
+    ground::Heightfield,
+    headless::run_ticks,
+    leg::LegCreature,
+    IKArm::{IKArm, IKArmTarget},
+};
+
+// Long enough for a default attack to wind up, strike and recover.
+const ATTACK_TICKS: u32 = 60;
+
+// A settled rig and its front leg attacking a target `from_foot` away from where that foot stands.
+fn attack(attack: Attack, from_foot: Vec3) -> (App, Entity, Entity, Vec<AttackHit>) {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = app.world().get::<LegCreature>(creature).unwrap().front_leg().unwrap();
+    let foot = app.world().get::<IKArm>(leg).unwrap().target;
+    let target = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_translation(foot + from_foot))).id();
+    app.world_mut().entity_mut(leg).insert((attack, IKArmTarget { target }));
+    let hits = run_collecting(&mut app, ATTACK_TICKS, |hit: &AttackHit| hit.clone());
+    (app, leg, target, hits)
+}
+
+#[test]
+fn strikes_a_target_in_reach_once() {
+    let (app, leg, target, hits) = attack(Attack::default(), Vec3::new(0., 0.1, 0.25));
+
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].leg, hits[0].target), (leg, target));
+    let target_pos = app.world().get::<GlobalTransform>(target).unwrap().translation();
+    assert!(hits[0].position.distance(target_pos) <= Attack::default().hit_tolerance);
+    // Back in the gait once it has recovered.
+    assert!(app.world().get::<Attack>(leg).is_none());
+    assert!(app.world().get::<IKArmTarget>(leg).is_none());
+}
+
+#[test]
+fn misses_a_target_out_of_reach() {
+    let (.., hits) = attack(Attack::default(), Vec3::Z * 1.5);
+
+    assert!(hits.is_empty());
+}
+
+#[test]
+fn misses_a_target_the_leg_is_too_short_for() {
+    // Within reach of the attack, but further than the bones go.
+    let mut long_reach = Attack::default();
+    long_reach.reach = 2.;
+    let (.., hits) = attack(long_reach, Vec3::Z);
+
+    assert!(hits.is_empty());
+}
+
+#[test]
+fn phases_can_be_zero_long() {
+    let mut instant = Attack::default();
+    (instant.wind_up, instant.strike, instant.recovery) = (0., 0., 0.);
+    let (app, leg, ..) = attack(instant, Vec3::new(0., 0.1, 0.25));
+
+    assert!(app.world().get::<IKArm>(leg).unwrap().target.is_finite());
+    assert!(app.world().get::<Attack>(leg).is_none());
+}
+
+#[test]
+fn gives_up_on_a_despawned_target() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = app.world().get::<LegCreature>(creature).unwrap().front_leg().unwrap();
+    let target = app.world_mut().spawn(TransformBundle::default()).id();
+    app.world_mut().entity_mut(leg).insert((Attack::default(), IKArmTarget { target }));
+    run_ticks(&mut app, 5);
+    app.world_mut().despawn(target);
+    run_ticks(&mut app, 2);
+
+    assert!(app.world().get::<Attack>(leg).is_none());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{climb::Ledge, ground::Heightfield, headless::run_ticks, jump::Airborne, leg::LegCreature};
+
+const LEDGE_START: f32 = 1.;
+
+// Flat ground rising or dropping by `height` from LEDGE_START on.
+fn ledge(height: f32) -> Heightfield {
+    Heightfield::new(move |point| if point.y >= LEDGE_START { height } else { 0. })
+}
+
+fn ledge_ahead(app: &App, creature: Entity) -> Ledge {
+    app.world().get::<LegCreature>(creature).unwrap().ledge()
+}
+
+// How far the body's front is pitched up, in radians.
+fn pitch(app: &App, creature: Entity) -> f32 {
+    let forward = app.world().get::<Transform>(creature).unwrap().forward();
+    // Bevy's forward is -Z, the rig walks along +Z.
+    (-forward.y).asin()
+}
+
+#[test]
+fn climbs_onto_a_ledge_nose_up() {
+    let mut app = app_on(ledge(0.3));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let (mut saw_ledge, mut most_pitch) = (false, 0_f32);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        assert!(app.world().get::<Airborne>(creature).is_none(), "tick {tick}: fell");
+        saw_ledge |= matches!(ledge_ahead(app, creature), Ledge::Up { .. });
+        most_pitch = most_pitch.max(pitch(app, creature));
+    });
+
+    assert!(saw_ledge);
+    assert!(most_pitch > 0.1, "pitched up by at most {most_pitch}");
+    assert!(body(&app, creature).z > LEDGE_START + 0.5, "only got to {}", body(&app, creature));
+    set_heading(&mut app, creature, Vec3::ZERO);
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.05, "body at {} on top", body_height(&app, creature));
+    assert!(worst_planted_foot_error(&app, creature) < 0.001);
+    assert!(pitch(&app, creature).abs() < 0.05, "still pitched by {}", pitch(&app, creature));
+}
+
+#[test]
+fn stops_in_front_of_a_ledge_too_tall_to_climb() {
+    let mut app = app_on(ledge(1.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let mut blocked = false;
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        blocked |= matches!(ledge_ahead(app, creature), Ledge::Blocked { .. });
+        assert!(worst_planted_foot_error(app, creature) < 0.001, "tick {tick}: planted foot off the ground");
+    });
+
+    assert!(blocked);
+    assert!(body(&app, creature).z < LEDGE_START, "walked on to {}", body(&app, creature));
+    assert!(feet(&app, creature).iter().all(|foot| foot.position.y.abs() < 0.001));
+}
+
+#[test]
+fn steps_down_a_drop_nose_down() {
+    let mut app = app_on(ledge(-0.3));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let (mut saw_drop, mut least_pitch) = (false, 0_f32);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        assert!(app.world().get::<Airborne>(creature).is_none(), "tick {tick}: fell");
+        saw_drop |= matches!(ledge_ahead(app, creature), Ledge::Down { .. });
+        least_pitch = least_pitch.min(pitch(app, creature));
+    });
+
+    assert!(saw_drop);
+    assert!(least_pitch < -0.1, "pitched down by at most {}", -least_pitch);
+    assert!(body(&app, creature).z > LEDGE_START + 0.5, "only got to {}", body(&app, creature));
+    set_heading(&mut app, creature, Vec3::ZERO);
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.05, "body at {} at the bottom", body_height(&app, creature));
+}
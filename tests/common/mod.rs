@@ -0,0 +1,100 @@
+// Shared by the integration tests: a headless app on a heightfield, one rig, and readers for its state.
+#![allow(dead_code)]
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use zombies::{
+    ground::{GroundQuery, Heightfield, HeightfieldGround},
+    headless::{run_ticks, spawn_rig, HeadlessPlugin},
+    leg::{IKLeg, LegCreature, LegSide},
+    LocomotionPlugin,
+    IKArm::IKArm,
+};
+
+// Long enough for the rig to drop onto the ground and plant every foot.
+pub const SETTLE_TICKS: u32 = 90;
+
+pub struct Foot {
+    pub position: Vec3,
+    pub end_effector: Vec3,
+    pub stepping: bool,
+    pub side: LegSide,
+}
+
+pub fn app_on(heightfield: Heightfield) -> App {
+    app_with_ground::<HeightfieldGround>(heightfield)
+}
+
+// app_on with another ground query backend, which may read the heightfield too.
+pub fn app_with_ground<G: SystemParam + 'static>(heightfield: Heightfield) -> App
+where
+    for<'w, 's> G::Item<'w, 's>: GroundQuery,
+{
+    let mut app = App::new();
+    app.add_plugins(HeadlessPlugin)
+        .insert_resource(heightfield)
+        .add_plugins(LocomotionPlugin::<G>::with_ground());
+    app
+}
+
+// A rig standing on the ground at `position`, already settled.
+pub fn settled_rig(app: &mut App, position: Vec3) -> Entity {
+    let height = app.world().resource::<Heightfield>().height_at(position.xz());
+    let creature = spawn_rig(app.world_mut(), position.with_y(height + 0.2));
+    run_ticks(app, SETTLE_TICKS);
+    creature
+}
+
+pub fn set_heading(app: &mut App, creature: Entity, target_offset: Vec3) {
+    app.world_mut().get_mut::<LegCreature>(creature).unwrap().set_target_offset(target_offset);
+}
+
+pub fn body(app: &App, creature: Entity) -> Vec3 {
+    app.world().get::<Transform>(creature).unwrap().translation
+}
+
+// Height of the body above the ground right under it.
+pub fn body_height(app: &App, creature: Entity) -> f32 {
+    let body = body(app, creature);
+    body.y - app.world().resource::<Heightfield>().height_at(body.xz())
+}
+
+pub fn feet(app: &App, creature: Entity) -> Vec<Foot> {
+    let world = app.world();
+    world.get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| {
+        let arm = world.get::<IKArm>(*leg_entity).unwrap();
+        let leg = world.get::<IKLeg>(*leg_entity).unwrap();
+        Foot { position: arm.target, end_effector: arm.end_effector(), stepping: leg.is_stepping(), side: leg.leg_side }
+    }).collect()
+}
+
+// How far the planted feet are from the surface right under them, at worst. A foot right on the edge of a step counts
+// as standing on either side of it.
+pub fn worst_planted_foot_error(app: &App, creature: Entity) -> f32 {
+    const EDGE: f32 = 0.01;
+    let heightfield = app.world().resource::<Heightfield>();
+    feet(app, creature).iter()
+        .filter(|foot| !foot.stepping)
+        .map(|foot| [Vec2::ZERO, Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y].iter()
+            .map(|nudge| (foot.position.y - heightfield.height_at(foot.position.xz() + *nudge * EDGE)).abs())
+            .fold(f32::INFINITY, f32::min))
+        .fold(0., f32::max)
+}
+
+// Runs one tick at a time, handing every tick to `check`.
+pub fn walk(app: &mut App, creature: Entity, target_offset: Vec3, ticks: u32, mut check: impl FnMut(&App, u32)) {
+    set_heading(app, creature, target_offset);
+    for tick in 0..ticks {
+        run_ticks(app, 1);
+        check(app, tick);
+    }
+}
+
+// Runs `ticks` ticks and collects what `map` makes of every `E` sent along the way.
+pub fn run_collecting<E: Event, T>(app: &mut App, ticks: u32, map: impl Fn(&E) -> T) -> Vec<T> {
+    let mut collected = Vec::new();
+    for _ in 0..ticks {
+        run_ticks(app, 1);
+        collected.extend(app.world().resource::<Events<E>>().iter_current_update_events().map(&map));
+    }
+    collected
+}
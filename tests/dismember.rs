@@ -0,0 +1,136 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    dismember::{CreatureCollapsed, DisabledLeg, LegLossResponse, LegLost, SeveredLeg},
+    ground::Heightfield,
+    headless::{run_ticks, spawn_rig_leg},
+    leg::{IKLeg, LegCreature, LegOf, LegSide},
+    IKArm::IKArm,
+};
+
+fn legs(app: &App, creature: Entity) -> Vec<Entity> {
+    app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect()
+}
+
+#[test]
+fn a_disabled_leg_hangs_while_the_others_walk_on_a_lower_body() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let disabled = legs(&app, creature)[0];
+    app.world_mut().entity_mut(disabled).insert(DisabledLeg);
+    let mut sides = Vec::new();
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 200, |app, tick| {
+        let foot = app.world().get::<IKArm>(disabled).unwrap().target;
+        if tick > 60 {
+            assert!(foot.y > 0.01, "tick {tick}: disabled foot down at {foot}");
+        }
+        let stepping: Vec<LegSide> = legs(app, creature).into_iter()
+            .filter(|leg| *leg != disabled)
+            .map(|leg| app.world().get::<IKLeg>(leg).unwrap())
+            .filter(|leg| leg.is_stepping())
+            .map(|leg| leg.leg_side)
+            .collect();
+        if let Some(side) = stepping.first().filter(|side| sides.last() != Some(*side)) {
+            sides.push(*side);
+        }
+    });
+    assert!(body(&app, creature).z > 2., "only got to {}", body(&app, creature));
+    assert!(body(&app, creature).x.abs() < 0.5, "veered off to {}", body(&app, creature));
+    assert!(sides.len() > 6, "gait went {sides:?}");
+
+    // One leg short sags the body by sag_per_leg.
+    set_heading(&mut app, creature, Vec3::ZERO);
+    run_ticks(&mut app, SETTLE_TICKS);
+    let sagged = 0.2 * (1. - LegLossResponse::default().sag_per_leg);
+    // Standing still stays put, rather than drifting towards the side with more feet.
+    let stopped = body(&app, creature);
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!(body(&app, creature).distance(stopped) < 0.05, "drifted from {stopped} to {}", body(&app, creature));
+    assert!((body_height(&app, creature) - sagged).abs() < 0.02, "body at {}", body_height(&app, creature));
+
+    app.world_mut().entity_mut(disabled).remove::<DisabledLeg>();
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.02, "body at {}", body_height(&app, creature));
+    assert!(app.world().get::<IKArm>(disabled).unwrap().target.y.abs() < 0.001);
+}
+
+#[test]
+fn a_severed_leg_leaves_its_creature_and_falls_to_the_ground() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let severed = legs(&app, creature)[0];
+    app.world_mut().entity_mut(severed).insert(SeveredLeg::new(Vec3::new(1., 2., 0.)));
+    let lost = run_collecting(&mut app, 1, |lost: &LegLost| (lost.creature, lost.leg));
+
+    assert_eq!(lost, vec![(creature, severed)]);
+    assert!(!legs(&app, creature).contains(&severed));
+    assert!(app.world().get::<IKLeg>(severed).is_none());
+    assert!(app.world().get::<LegOf>(severed).is_none());
+
+    let mut highest = 0_f32;
+    for _ in 0..120 {
+        run_ticks(&mut app, 1);
+        highest = highest.max(app.world().get::<Transform>(severed).unwrap().translation.y);
+    }
+    let landed = app.world().get::<Transform>(severed).unwrap().translation;
+    assert!(highest > 0.2, "never flew up");
+    assert!(landed.x > 0.5, "landed at {landed}");
+    assert!((landed.y - 0.05).abs() < 0.01, "landed at {landed}");
+    // The body stays up on three legs.
+    assert!(!app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+}
+
+#[test]
+fn too_few_legs_collapse_the_body() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let legs = legs(&app, creature);
+    app.world_mut().entity_mut(legs[0]).insert(SeveredLeg::new(Vec3::ZERO));
+    app.world_mut().entity_mut(legs[1]).insert(DisabledLeg);
+    let collapsed = run_collecting(&mut app, 2, |collapsed: &CreatureCollapsed| collapsed.creature);
+
+    assert_eq!(collapsed, vec![creature]);
+    assert!(app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+    run_ticks(&mut app, SETTLE_TICKS);
+    let collapsed_height = LegLossResponse::default().collapsed_height;
+    assert!((body_height(&app, creature) - collapsed_height).abs() < 0.02, "body at {}", body_height(&app, creature));
+
+    // Walking again on the disabled leg gets it back up.
+    app.world_mut().entity_mut(legs[1]).remove::<DisabledLeg>();
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!(!app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+    assert!(body_height(&app, creature) > 0.15, "body at {}", body_height(&app, creature));
+}
+
+#[test]
+fn legs_all_on_one_side_collapse_the_body() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let body = body(&app, creature);
+    // Two more legs down the middle of the +X side, six in all.
+    for z in [0.05, -0.05] {
+        let offset = Vec3::new(0.15, -0.1, z);
+        let leg = spawn_rig_leg(app.world_mut(), body + offset, IKLeg::new(Vec3::new(0.5, -0.1, z), 0.1, 0.15, 0.3, LegSide::Left, false));
+        app.world_mut().entity_mut(leg).insert(LegOf { creature, offset });
+    }
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!(!app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+
+    // Four legs left is plenty by count, but they're all off to one side of the body.
+    let other_side: Vec<Entity> = app.world().get::<LegCreature>(creature).unwrap().legs_info.iter()
+        .filter(|(_, offset)| offset.x < 0.)
+        .map(|(leg_entity, _)| *leg_entity)
+        .collect();
+    assert_eq!(other_side.len(), 2);
+    for leg in other_side {
+        app.world_mut().entity_mut(leg).insert(SeveredLeg::new(Vec3::ZERO));
+    }
+    let collapsed = run_collecting(&mut app, 2, |collapsed: &CreatureCollapsed| collapsed.creature);
+
+    assert_eq!(legs(&app, creature).len(), 4);
+    assert_eq!(collapsed, vec![creature]);
+    assert!(app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{ground::Heightfield, headless::run_ticks, leg::LegSide};
+
+#[test]
+fn settles_at_body_height_with_every_foot_planted() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.02, "body at {}", body_height(&app, creature));
+    for foot in feet(&app, creature) {
+        assert!(!foot.stepping);
+        assert!(foot.position.y.abs() < 0.001, "foot at {}", foot.position);
+        // The two-bone chain reaches every planted foot.
+        assert!(foot.end_effector.distance(foot.position) < 0.01, "end effector {} for foot {}", foot.end_effector, foot.position);
+    }
+}
+
+#[test]
+fn stands_still_without_input() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let start = body(&app, creature);
+
+    run_ticks(&mut app, 120);
+    assert!(body(&app, creature).distance(start) < 0.01);
+    assert!(feet(&app, creature).iter().all(|foot| !foot.stepping));
+}
+
+#[test]
+fn walks_forward_stepping_one_diagonal_pair_at_a_time() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let mut sides_stepped = Vec::new();
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        let stepping: Vec<LegSide> = feet(app, creature).iter().filter(|foot| foot.stepping).map(|foot| foot.side).collect();
+        assert!(stepping.windows(2).all(|pair| pair[0] == pair[1]), "tick {tick}: both sides stepping at once");
+        if let Some(side) = stepping.first().filter(|side| sides_stepped.last() != Some(*side)) {
+            sides_stepped.push(*side);
+        }
+        let height = body_height(app, creature);
+        assert!((0.15..0.35).contains(&height), "tick {tick}: body at {height}");
+        assert!(worst_planted_foot_error(app, creature) < 0.001, "tick {tick}: planted foot off the ground");
+    });
+
+    assert!(body(&app, creature).z > 5., "only got to {}", body(&app, creature));
+    // Left and right pairs take turns.
+    assert!(sides_stepped.len() > 10, "gait went {sides_stepped:?}");
+}
+
+#[test]
+fn same_inputs_give_bit_identical_results() {
+    let run = || {
+        let mut app = app_on(Heightfield::flat(0.));
+        let creature = settled_rig(&mut app, Vec3::ZERO);
+        walk(&mut app, creature, Vec3::Z * 0.4, 100, |_, _| {});
+        walk(&mut app, creature, Vec3::X * 0.4, 100, |_, _| {});
+        let transform = *app.world().get::<Transform>(creature).unwrap();
+        let feet: Vec<Vec3> = feet(&app, creature).iter().map(|foot| foot.position).collect();
+        (transform, feet)
+    };
+    let (first_transform, first_feet) = run();
+    let (second_transform, second_feet) = run();
+
+    assert_eq!(first_transform.translation.to_array().map(f32::to_bits), second_transform.translation.to_array().map(f32::to_bits));
+    assert_eq!(first_transform.rotation.to_array().map(f32::to_bits), second_transform.rotation.to_array().map(f32::to_bits));
+    for (first, second) in first_feet.iter().zip(&second_feet) {
+        assert_eq!(first.to_array().map(f32::to_bits), second.to_array().map(f32::to_bits));
+    }
+}
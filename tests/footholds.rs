@@ -0,0 +1,111 @@
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use zombies::{
+    foothold::{find_foothold, Foothold, FootholdRequest, FootholdSearch, SurfaceQuery, SurfaceRules},
+    ground::{GroundHit, GroundQuery, Heightfield},
+};
+
+// Searches around `desired_pos` for a creature standing 0.3 behind it.
+fn search_with(heightfield: Heightfield, search: FootholdSearch, desired_pos: Vec3, ray_budget: usize) -> Option<Foothold> {
+    let mut world = World::new();
+    world.insert_resource(heightfield);
+    world.init_resource::<SurfaceRules>();
+    world.run_system_once(move |mut ground: Res<Heightfield>, surfaces: SurfaceQuery| {
+        let transform = GlobalTransform::from_translation(desired_pos + Vec3::new(0., 0.2, -0.3));
+        let request = FootholdRequest { search: &search, transform: &transform, up: Vec3::Y, desired_pos, ray_budget };
+        find_foothold(&mut ground, &surfaces, &request, None)
+    })
+}
+
+fn search(heightfield: Heightfield, desired_pos: Vec3) -> Option<Foothold> {
+    search_with(heightfield, FootholdSearch::default(), desired_pos, usize::MAX)
+}
+
+// A ridge along Z, too steep to stand on within 0.06 of x = 0 and flat past that.
+fn ridge() -> Heightfield {
+    Heightfield::new(|point| 3. * (0.06 - point.x.abs()).max(0.))
+}
+
+#[test]
+fn plants_right_where_it_wants_on_flat_ground() {
+    let foothold = search(Heightfield::flat(0.5), Vec3::new(1., 0.5, 2.)).unwrap();
+
+    assert!(foothold.position.distance(Vec3::new(1., 0.5, 2.)) < 0.001, "planted at {}", foothold.position);
+    assert!(foothold.normal.angle_between(Vec3::Y) < 0.001);
+}
+
+#[test]
+fn moves_off_ground_too_steep_to_stand_on() {
+    let search_settings = FootholdSearch::default();
+    let foothold = search(ridge(), Vec3::new(0.03, 0., 0.)).unwrap();
+
+    // The closest probe that's off the ridge, a ring probe away.
+    assert!(foothold.position.x.abs() >= 0.06, "planted on the ridge at {}", foothold.position);
+    assert!(foothold.normal.angle_between(Vec3::Y) < search_settings.max_slope);
+    assert!(foothold.position.xz().distance(Vec2::new(0.03, 0.)) <= search_settings.probe_radius + 0.001);
+}
+
+#[test]
+fn keeps_off_the_lip_of_a_step() {
+    let step = || Heightfield::new(|point| if point.x < 0. { 0.1 } else { 0. });
+    let foothold = search(step(), Vec3::new(0.005, 0., 0.)).unwrap();
+
+    assert!(foothold.position.x.abs() > 0.03, "planted on the lip at {}", foothold.position);
+    assert!(foothold.normal.angle_between(Vec3::Y) < 0.001);
+}
+
+#[test]
+fn slope_weight_trades_flatness_for_distance() {
+    // A 30 degree ramp that levels off a ring probe away.
+    let ramp = || Heightfield::new(|point| point.x.clamp(-0.04, 0.04) * 30_f32.to_radians().tan());
+    let near = search_with(ramp(), FootholdSearch::default(), Vec3::ZERO, usize::MAX).unwrap();
+    let flat = search_with(ramp(), FootholdSearch { slope_weight: 10., ..default() }, Vec3::ZERO, usize::MAX).unwrap();
+
+    assert!(near.position.xz().length() < 0.001, "went to {}", near.position);
+    assert!(flat.normal.angle_between(Vec3::Y) < 0.001, "went to {} facing {}", flat.position, flat.normal);
+}
+
+#[test]
+fn a_smaller_ray_budget_gives_up_sooner() {
+    let desired_pos = Vec3::new(0.03, 0., 0.);
+
+    assert!(search_with(ridge(), FootholdSearch::default(), desired_pos, 0).is_none());
+    // Just the ray under the desired position, which is on the ridge.
+    assert!(search_with(ridge(), FootholdSearch::default(), desired_pos, 1).is_none());
+    // One ring probe is enough to get off it.
+    let foothold = search_with(ridge(), FootholdSearch::default(), desired_pos, 4).unwrap();
+    assert!(foothold.position.x.abs() >= 0.06, "planted on the ridge at {}", foothold.position);
+}
+
+// A wall wherever it's probed, so every ray hits and every hit is rejected. Counts the rays.
+struct WallEverywhere {
+    rays: usize,
+}
+
+impl GroundQuery for WallEverywhere {
+    fn cast_ground_ray(&mut self, ray: Ray3d, _max_distance: f32, _filter: &dyn Fn(Entity) -> bool) -> Option<GroundHit> {
+        self.rays += 1;
+        Some(GroundHit { entity: None, position: ray.origin, normal: Vec3::X, distance: 0. })
+    }
+}
+
+#[test]
+fn the_fallback_search_stays_within_the_ray_budget() {
+    let rays_cast = |ray_budget: usize| {
+        let mut world = World::new();
+        world.init_resource::<SurfaceRules>();
+        world.run_system_once(move |surfaces: SurfaceQuery| {
+            let mut ground = WallEverywhere { rays: 0 };
+            let search = FootholdSearch::default();
+            let transform = GlobalTransform::from_translation(Vec3::new(0., 0.2, -0.3));
+            let request = FootholdRequest { search: &search, transform: &transform, up: Vec3::Y, desired_pos: Vec3::ZERO, ray_budget };
+            assert!(find_foothold(&mut ground, &surfaces, &request, None).is_none());
+            ground.rays
+        })
+    };
+
+    for ray_budget in 0..12 {
+        assert!(rays_cast(ray_budget) <= ray_budget, "cast {} rays on a budget of {ray_budget}", rays_cast(ray_budget));
+    }
+    // Without a limit, the whole ring and then both fallback rays.
+    assert_eq!(rays_cast(usize::MAX), FootholdSearch::default().probe_count + 1 + 2);
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    attack::Attack,
+    foothold::SurfaceTag,
+    footstep::{FootLifted, FootPlanted, FootstepAudioPlugin, FootstepSounds},
+    grab::Grab,
+    ground::Heightfield,
+    headless::run_ticks,
+    health::{Damage, DeathPose, DeathResponse, Health},
+    jump::AirborneLeg,
+    leg::LegCreature,
+    ragdoll::CurledLeg,
+    IKArm::IKArmTarget,
+};
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    Lifted(Entity),
+    Planted(Entity),
+}
+
+// Footstep events over `ticks` ticks, in the order they were sent.
+fn steps(app: &mut App, ticks: u32) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for _ in 0..ticks {
+        run_ticks(app, 1);
+        let world = app.world();
+        steps.extend(world.resource::<Events<FootLifted>>().iter_current_update_events().map(|lifted| Step::Lifted(lifted.leg)));
+        steps.extend(world.resource::<Events<FootPlanted>>().iter_current_update_events().map(|planted| Step::Planted(planted.leg)));
+    }
+    steps
+}
+
+// Walks until a foot plants and returns its leg, with the plant just sent.
+fn walk_until_planted(app: &mut App, creature: Entity) -> Entity {
+    set_heading(app, creature, Vec3::Z * 0.4);
+    for _ in 0..100 {
+        run_ticks(app, 1);
+        if let Some(planted) = app.world().resource::<Events<FootPlanted>>().iter_current_update_events().next() {
+            return planted.leg;
+        }
+    }
+    panic!("no foot planted");
+}
+
+// Takes a leg out of the gait right as it plants, the plant mustn't be heard again while it's out.
+fn leaving_the_gait_is_quiet(leave: impl FnOnce(&mut App, Entity, Entity)) {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = walk_until_planted(&mut app, creature);
+    leave(&mut app, creature, leg);
+    let steps = steps(&mut app, 10);
+
+    assert!(!steps.contains(&Step::Planted(leg)), "leg {leg} made {steps:?}");
+}
+
+#[test]
+fn attacking_legs_make_no_footsteps() {
+    leaving_the_gait_is_quiet(|app, _, leg| {
+        let target = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_xyz(0., 0., 1.))).id();
+        app.world_mut().entity_mut(leg).insert((Attack::default(), IKArmTarget { target }));
+    });
+}
+
+#[test]
+fn grabbing_legs_make_no_footsteps() {
+    leaving_the_gait_is_quiet(|app, _, leg| {
+        let target = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_xyz(0., 1., 1.))).id();
+        app.world_mut().entity_mut(leg).insert((Grab::default(), IKArmTarget { target }));
+    });
+}
+
+// Jumping and dying take the legs out a tick later, these put them straight into the state they end up in.
+#[test]
+fn airborne_legs_make_no_footsteps() {
+    leaving_the_gait_is_quiet(|app, _, leg| {
+        app.world_mut().entity_mut(leg).insert(AirborneLeg);
+    });
+}
+
+#[test]
+fn curled_legs_make_no_footsteps() {
+    leaving_the_gait_is_quiet(|app, _, leg| {
+        app.world_mut().entity_mut(leg).insert(CurledLeg);
+    });
+}
+
+#[test]
+fn the_dead_make_no_footsteps() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    app.world_mut().entity_mut(creature).insert((Health::new(1.), DeathResponse { pose: DeathPose::Crumple, ..default() }));
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 30);
+    app.world_mut().send_event(Damage { entity: creature, amount: 1., position: Vec3::ZERO });
+    run_ticks(&mut app, 2);
+
+    assert_eq!(steps(&mut app, 60), vec![]);
+}
+
+#[test]
+fn standing_still_makes_no_footsteps() {
+    let mut app = app_on(Heightfield::flat(0.));
+    settled_rig(&mut app, Vec3::ZERO);
+
+    assert_eq!(steps(&mut app, 120), vec![]);
+}
+
+#[test]
+fn every_lifted_foot_plants_once() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    let steps = steps(&mut app, 200);
+
+    let legs: Vec<Entity> = app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect();
+    for leg in legs {
+        let leg_steps: Vec<&Step> = steps.iter().filter(|step| matches!(step, Step::Lifted(entity) | Step::Planted(entity) if *entity == leg)).collect();
+        assert!(leg_steps.len() > 10, "leg {leg} only made {leg_steps:?}");
+        // Lifted and planted take turns, starting with a lift.
+        for (i, step) in leg_steps.iter().enumerate() {
+            let expected = if i % 2 == 0 { Step::Lifted(leg) } else { Step::Planted(leg) };
+            assert_eq!(**step, expected, "step {i} of leg {leg}");
+        }
+    }
+}
+
+#[test]
+fn planted_events_say_where_and_on_what() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    let planted = run_collecting(&mut app, 100, |planted: &FootPlanted| planted.clone());
+
+    assert!(!planted.is_empty());
+    for planted in planted {
+        assert_eq!(planted.creature, creature);
+        assert_eq!(planted.surface, SurfaceTag::Walkable);
+        assert!(planted.position.y.abs() < 0.001, "planted at {}", planted.position);
+        assert!(planted.normal.angle_between(Vec3::Y) < 0.001);
+        assert!(planted.impact_speed.is_finite() && planted.impact_speed >= 0.);
+    }
+}
+
+#[test]
+fn footstep_audio_plays_a_sound_per_planted_foot() {
+    let mut app = app_on(Heightfield::flat(0.));
+    app.add_plugins(FootstepAudioPlugin)
+        .insert_resource(FootstepSounds { default: Some(Handle::default()), full_volume_speed: 1., ..default() });
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    let planted = run_collecting(&mut app, 100, |planted: &FootPlanted| planted.position);
+    run_ticks(&mut app, 1);
+
+    let mut sounds = app.world_mut().query_filtered::<&Transform, With<PlaybackSettings>>();
+    let sounds: Vec<Vec3> = sounds.iter(app.world()).map(|transform| transform.translation).collect();
+    assert_eq!(sounds.len(), planted.len());
+    assert!(planted.iter().all(|position| sounds.contains(position)));
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{climb::{ClimbSettings, Ledge}, ground::Heightfield, headless::run_ticks, jump::{Airborne, Jump}, leg::LegCreature};
+
+const GAP_START: f32 = 1.;
+const GAP_DEPTH: f32 = 2.;
+
+// Flat ground with a trench across the path from GAP_START, `width` wide.
+fn gap(width: f32) -> Heightfield {
+    Heightfield::new(move |point| if point.y > GAP_START && point.y < GAP_START + width { -GAP_DEPTH } else { 0. })
+}
+
+#[test]
+fn steps_over_a_gap_narrower_than_its_stride() {
+    let mut app = app_on(gap(0.15));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 200, |app, tick| {
+        assert!(app.world().get::<Airborne>(creature).is_none(), "tick {tick}: fell in");
+        for foot in feet(app, creature).iter().filter(|foot| !foot.stepping) {
+            assert!(foot.position.y > -0.001, "tick {tick}: foot planted in the gap at {}", foot.position);
+        }
+    });
+
+    assert!(body(&app, creature).z > GAP_START + 1., "only got to {}", body(&app, creature));
+}
+
+#[test]
+fn waits_at_the_edge_of_a_gap_too_wide_to_step_over() {
+    let mut app = app_on(gap(2.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        assert!(app.world().get::<Airborne>(creature).is_none(), "tick {tick}: fell in");
+        assert!(worst_planted_foot_error(app, creature) < 0.001, "tick {tick}: planted foot off the ground");
+    });
+
+    assert!(body(&app, creature).z < GAP_START, "walked on to {}", body(&app, creature));
+    assert!(feet(&app, creature).iter().all(|foot| foot.position.z < GAP_START));
+}
+
+#[test]
+fn sees_a_blocked_drop_at_a_wide_gap() {
+    let mut app = app_on(gap(2.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    app.world_mut().entity_mut(creature).insert(ClimbSettings { block_drops: true, ..default() });
+    let mut blocked = false;
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, _| {
+        let ledge = app.world().get::<LegCreature>(creature).unwrap().ledge();
+        blocked |= matches!(ledge, Ledge::Blocked { .. });
+    });
+
+    assert!(blocked);
+    assert!(body(&app, creature).z < GAP_START, "walked on to {}", body(&app, creature));
+}
+
+#[test]
+fn jumps_a_wide_gap_and_lands_on_the_far_side() {
+    let mut app = app_on(gap(2.));
+    let creature = settled_rig(&mut app, Vec3::new(0., 0., 0.5));
+    app.world_mut().entity_mut(creature).insert(Jump::new(Vec3::new(0., 3., 4.5)));
+    let mut took_off = false;
+
+    run_ticks(&mut app, 1);
+    for _ in 0..120 {
+        run_ticks(&mut app, 1);
+        took_off |= app.world().get::<Airborne>(creature).is_some();
+    }
+
+    assert!(took_off);
+    assert!(app.world().get::<Airborne>(creature).is_none(), "still in the air at {}", body(&app, creature));
+    assert!(body(&app, creature).z > GAP_START + 2., "landed at {}", body(&app, creature));
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.02, "body at {}", body_height(&app, creature));
+    assert!(worst_planted_foot_error(&app, creature) < 0.001);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    grab::{Carried, Grab, Grabbed, Released},
+    ground::Heightfield,
+    headless::run_ticks,
+    leg::{IKLeg, LegCreature},
+    IKArm::{IKArm, IKArmTarget},
+};
+
+// A settled rig with its front leg reaching for an object `from_foot` away from where that foot stands.
+fn reach(from_foot: Vec3) -> (App, Entity, Entity, Entity) {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = app.world().get::<LegCreature>(creature).unwrap().front_leg().unwrap();
+    let foot = app.world().get::<IKArm>(leg).unwrap().target;
+    let object = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_translation(foot + from_foot))).id();
+    app.world_mut().entity_mut(leg).insert((Grab::default(), IKArmTarget { target: object }));
+    (app, creature, leg, object)
+}
+
+// reach, once the object is in hand.
+fn held(from_foot: Vec3) -> (App, Entity, Entity, Entity) {
+    let (mut app, creature, leg, object) = reach(from_foot);
+    let grabbed = run_collecting(&mut app, 60, |grabbed: &Grabbed| (grabbed.arm, grabbed.object));
+    assert_eq!(grabbed, vec![(leg, object)]);
+    (app, creature, leg, object)
+}
+
+#[test]
+fn picks_up_and_carries_an_object_in_reach() {
+    let (mut app, creature, leg, object) = held(Vec3::new(0., 0.1, 0.2));
+    walk(&mut app, creature, Vec3::Z * 0.4, 60, |app, tick| {
+        assert!(!app.world().get::<IKLeg>(leg).unwrap().is_stepping(), "tick {tick}: holding leg stepping");
+    });
+
+    assert_eq!(app.world().get::<Carried>(object).unwrap().carrier(), leg);
+    let object_pos = app.world().get::<Transform>(object).unwrap().translation;
+    let hand = app.world().get::<IKArm>(leg).unwrap().end_effector();
+    assert!(object_pos.distance(hand) <= Grab::default().tolerance, "carried at {object_pos}, hand at {hand}");
+    assert!(body(&app, creature).z > 0.5, "only got to {}", body(&app, creature));
+}
+
+#[test]
+fn gives_up_on_an_object_out_of_reach() {
+    let (mut app, _, leg, object) = reach(Vec3::Z * 2.);
+    let grabbed = run_collecting(&mut app, 200, |grabbed: &Grabbed| grabbed.object);
+
+    assert!(grabbed.is_empty());
+    assert!(app.world().get::<Grab>(leg).is_none());
+    assert!(app.world().get::<Carried>(object).is_none());
+}
+
+#[test]
+fn thrown_objects_land_on_the_ground() {
+    let (mut app, _, leg, object) = held(Vec3::new(0., 0.1, 0.2));
+    app.world_mut().get_mut::<Grab>(leg).unwrap().throw(Vec3::new(0., 2., 1.));
+    let released = run_collecting(&mut app, 2, |released: &Released| (released.arm, released.object));
+    let thrown_from = app.world().get::<Transform>(object).unwrap().translation;
+    run_ticks(&mut app, 120);
+
+    assert_eq!(released, vec![(leg, object)]);
+    assert!(app.world().get::<Grab>(leg).is_none());
+    let landed = app.world().get::<Transform>(object).unwrap().translation;
+    assert!(landed.y.abs() < 0.001, "landed at {landed}");
+    assert!(landed.z > thrown_from.z + 0.2, "thrown from {thrown_from} to {landed}");
+}
+
+#[test]
+fn a_despawned_object_frees_the_leg() {
+    let (mut app, creature, leg, object) = held(Vec3::new(0., 0.1, 0.2));
+    app.world_mut().despawn(object);
+    run_ticks(&mut app, 2);
+
+    assert!(app.world().get::<Grab>(leg).is_none());
+    assert!(app.world().get::<IKArmTarget>(leg).is_none());
+    let mut stepped = false;
+    walk(&mut app, creature, Vec3::Z * 0.4, 60, |app, _| {
+        stepped |= app.world().get::<IKLeg>(leg).unwrap().is_stepping();
+    });
+    assert!(stepped, "leg never rejoined the gait");
+}
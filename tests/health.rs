@@ -0,0 +1,135 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    dismember::LegLossResponse,
+    ground::Heightfield,
+    headless::run_ticks,
+    health::{CreatureDied, Damage, DamageTaken, DeathPose, DeathResponse, Health, HitZone, LegDestroyed},
+    leg::{IKLeg, LegCreature},
+    ragdoll::{Crumple, CurledLeg},
+};
+
+fn legs(app: &App, creature: Entity) -> Vec<Entity> {
+    app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect()
+}
+
+fn hurt(app: &mut App, entity: Entity, amount: f32) {
+    app.world_mut().send_event(Damage { entity, amount, position: Vec3::ZERO });
+}
+
+// A settled rig with `health` on its body that dies the way `response` says.
+fn mortal_rig(app: &mut App, health: f32, response: DeathResponse) -> Entity {
+    let creature = settled_rig(app, Vec3::ZERO);
+    app.world_mut().entity_mut(creature).insert((Health::new(health), response));
+    creature
+}
+
+fn kill(app: &mut App, creature: Entity) {
+    hurt(app, creature, f32::MAX);
+    let died = run_collecting(app, 2, |died: &CreatureDied| died.creature);
+    assert_eq!(died, vec![creature]);
+}
+
+#[test]
+fn damage_to_a_leg_goes_to_the_body_through_its_hit_zone() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = mortal_rig(&mut app, 100., DeathResponse::default());
+    let leg = legs(&app, creature)[0];
+    app.world_mut().entity_mut(leg).insert(HitZone { multiplier: 2. });
+    hurt(&mut app, leg, 10.);
+    let taken = run_collecting(&mut app, 2, |taken: &DamageTaken| taken.clone());
+
+    assert_eq!(taken.len(), 1);
+    assert_eq!(taken[0].creature, Some(creature));
+    assert_eq!(taken[0].leg, None);
+    assert_eq!(taken[0].amount, 20.);
+    assert_eq!(taken[0].remaining, 80.);
+    assert_eq!(app.world().get::<Health>(creature).unwrap().current, 80.);
+}
+
+#[test]
+fn a_leg_with_its_own_health_is_destroyed_and_lost() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = mortal_rig(&mut app, 100., DeathResponse::default());
+    let leg = legs(&app, creature)[0];
+    app.world_mut().entity_mut(leg).insert(Health::new(5.));
+    hurt(&mut app, leg, 10.);
+    let destroyed = run_collecting(&mut app, 2, |destroyed: &LegDestroyed| (destroyed.creature, destroyed.leg));
+    run_ticks(&mut app, 2);
+
+    assert_eq!(destroyed, vec![(Some(creature), leg)]);
+    assert!(!legs(&app, creature).contains(&leg));
+    assert!(app.world().get::<IKLeg>(leg).is_none());
+    // The body wasn't touched.
+    assert_eq!(app.world().get::<Health>(creature).unwrap().current, 100.);
+}
+
+#[test]
+fn the_dead_take_no_more_damage() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = mortal_rig(&mut app, 10., DeathResponse::default());
+    kill(&mut app, creature);
+    hurt(&mut app, creature, 10.);
+
+    assert!(run_collecting(&mut app, 2, |taken: &DamageTaken| taken.amount).is_empty());
+}
+
+#[test]
+fn standing_dead_stay_where_they_died() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = mortal_rig(&mut app, 10., DeathResponse { pose: DeathPose::Stand, ..default() });
+    kill(&mut app, creature);
+    let died_at = body(&app, creature);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 120, |app, tick| {
+        for leg in legs(app, creature) {
+            assert!(!app.world().get::<IKLeg>(leg).unwrap().is_stepping(), "tick {tick}: leg {leg} stepping");
+        }
+    });
+
+    assert!(body(&app, creature).distance(died_at) < 0.01, "moved from {died_at} to {}", body(&app, creature));
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.02, "body at {}", body_height(&app, creature));
+    assert!(!app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+}
+
+#[test]
+fn collapsing_dead_sink_onto_their_feet() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = mortal_rig(&mut app, 10., DeathResponse { pose: DeathPose::Collapse, ..default() });
+    kill(&mut app, creature);
+    run_ticks(&mut app, SETTLE_TICKS);
+
+    assert!(app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+    let collapsed_height = LegLossResponse::default().collapsed_height;
+    assert!((body_height(&app, creature) - collapsed_height).abs() < 0.02, "body at {}", body_height(&app, creature));
+    assert!(app.world().get::<Crumple>(creature).is_none());
+}
+
+#[test]
+fn crumpling_dead_fall_and_curl_their_legs_up() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let response = DeathResponse { pose: DeathPose::Crumple, rest_height: 0.05, ..default() };
+    let creature = mortal_rig(&mut app, 10., response);
+    kill(&mut app, creature);
+    run_ticks(&mut app, SETTLE_TICKS);
+
+    assert!(app.world().get::<Crumple>(creature).unwrap().is_grounded());
+    assert!((body_height(&app, creature) - 0.05).abs() < 0.001, "body at {}", body_height(&app, creature));
+    for leg in legs(&app, creature) {
+        assert!(app.world().get::<CurledLeg>(leg).is_some());
+    }
+}
+
+#[test]
+fn the_dead_despawn_with_their_legs() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = mortal_rig(&mut app, 10., DeathResponse { despawn_after: Some(0.5), ..default() });
+    let legs = legs(&app, creature);
+    kill(&mut app, creature);
+    run_ticks(&mut app, 60);
+
+    assert!(app.world().get_entity(creature).is_none());
+    assert!(legs.iter().all(|leg| app.world().get_entity(*leg).is_none()));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use bevy::{prelude::*, reflect::GetPath};
+use common::*;
+use zombies::{
+    dismember::SeveredLeg,
+    ground::Heightfield,
+    headless::run_ticks,
+    inspect::is_read_only,
+    leg::{IKLeg, LegCreature},
+    lod::LocomotionLod,
+    perception::Perception,
+    IKArm::IKArm,
+};
+
+#[test]
+fn registers_every_locomotion_component() {
+    let app = app_on(Heightfield::flat(0.));
+    let registry = app.world().resource::<AppTypeRegistry>().read();
+    for name in ["IKArm", "IKArmTarget", "IKLeg", "LegCreature", "FootholdSearch", "ClimbSettings", "SurfaceTag", "LocomotionLod", "SeveredLeg", "Perception", "Perceivable"] {
+        let registration = registry.get_with_short_type_path(name).unwrap_or_else(|| panic!("{name} isn't registered"));
+        assert!(registration.data::<ReflectComponent>().is_some(), "{name} can't be reflected as a component");
+    }
+    assert!(registry.get_with_short_type_path("LegSide").is_some());
+    assert!(registry.get_with_short_type_path("LodTier").is_some());
+}
+
+#[test]
+fn marks_simulation_state_read_only() {
+    for field in ["step_start", "stepping", "step_elapsed"] {
+        assert!(is_read_only::<IKLeg>(field), "IKLeg::{field}");
+    }
+    assert!(is_read_only::<LegCreature>("target_offset"));
+    assert!(is_read_only::<IKArm>("end_effector"));
+    for field in ["since_update", "seen", "heard", "last_known"] {
+        assert!(is_read_only::<Perception>(field), "Perception::{field}");
+    }
+    assert!(is_read_only::<LocomotionLod>("baked_phase"));
+    assert!(is_read_only::<SeveredLeg>("landed"));
+    assert!(!is_read_only::<LocomotionLod>("tier"));
+    assert!(!is_read_only::<Perception>("view_distance"));
+    assert!(!is_read_only::<IKLeg>("step_distance"));
+    assert!(!is_read_only::<LegCreature>("target_height"));
+}
+
+#[test]
+fn picks_up_parameters_edited_through_reflection() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    // The same path an inspector takes, the component is never touched by type.
+    let registration = app.world().resource::<AppTypeRegistry>().read().get(std::any::TypeId::of::<LegCreature>()).unwrap().clone();
+    let reflect_component = registration.data::<ReflectComponent>().unwrap();
+    let mut leg_creature = reflect_component.reflect_mut(app.world_mut().entity_mut(creature)).unwrap();
+    *leg_creature.path_mut::<f32>("target_height").unwrap() = 0.3;
+
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.3).abs() < 0.02, "body at {}", body_height(&app, creature));
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use common::*;
+use zombies::{
+    ground::Heightfield,
+    headless::run_ticks,
+    interpolation::SimTransform,
+    leg::{IKLeg, LegCreature},
+    IKArm::{ArmRig, IKArm},
+};
+
+// An app rendering `frames_per_tick` frames for every fixed tick, with a settled rig. The harness renders one.
+fn app_at(frames_per_tick: u32) -> (App, Entity) {
+    let mut app = app_on(Heightfield::flat(0.));
+    let timestep = app.world().resource::<Time<Fixed>>().timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep / frames_per_tick));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    // Settling took SETTLE_TICKS frames, make up the ticks the slower app is short of.
+    run_ticks(&mut app, SETTLE_TICKS * (frames_per_tick - 1));
+    (app, creature)
+}
+
+fn legs(app: &App, creature: Entity) -> Vec<Entity> {
+    app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect()
+}
+
+fn simulated(app: &App, entity: Entity) -> Transform {
+    app.world().get::<SimTransform>(entity).unwrap().current()
+}
+
+// Where the rendered skeleton puts the tip of an arm.
+fn rendered_foot(app: &App, arm: Entity) -> Vec3 {
+    let tip = app.world().get::<ArmRig>(arm).unwrap().tip.unwrap();
+    app.world().get::<GlobalTransform>(tip).unwrap().translation()
+}
+
+#[test]
+fn the_simulation_is_the_same_at_any_frame_rate() {
+    let (mut once, creature) = app_at(1);
+    let (mut twice, _) = app_at(2);
+    set_heading(&mut once, creature, Vec3::Z * 0.4);
+    set_heading(&mut twice, creature, Vec3::Z * 0.4);
+    run_ticks(&mut once, 100);
+    run_ticks(&mut twice, 200);
+
+    assert_eq!(simulated(&once, creature), simulated(&twice, creature));
+    for leg in legs(&once, creature) {
+        assert_eq!(once.world().get::<IKArm>(leg).unwrap().target, twice.world().get::<IKArm>(leg).unwrap().target);
+    }
+}
+
+#[test]
+fn bodies_move_smoothly_between_ticks() {
+    let (mut app, creature) = app_at(2);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 40);
+    let mut last = body(&app, creature).z;
+    let mut moves = Vec::new();
+    for _ in 0..40 {
+        run_ticks(&mut app, 1);
+        moves.push(body(&app, creature).z - last);
+        last = body(&app, creature).z;
+    }
+
+    // Every frame moves it, not just the ones with a tick.
+    assert!(moves.iter().all(|step| *step > 0.), "moved by {moves:?}");
+}
+
+#[test]
+fn planted_feet_stay_put_between_ticks() {
+    const FRAMES_PER_TICK: u32 = 4;
+    let (mut app, creature) = app_at(FRAMES_PER_TICK);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 160);
+    let legs = legs(&app, creature);
+    // Frames each foot has been down for. A foot that has just landed is still blending out of the step, and one the
+    // body has walked out of reach of slides in the simulation too.
+    let mut planted_for = vec![0; legs.len()];
+    let (mut worst, mut samples) = (0_f32, 0);
+    for _ in 0..160 {
+        let feet: Vec<Vec3> = legs.iter().map(|leg| rendered_foot(&app, *leg)).collect();
+        run_ticks(&mut app, 1);
+        for (i, leg) in legs.iter().enumerate() {
+            let arm = app.world().get::<IKArm>(*leg).unwrap();
+            let planted = !app.world().get::<IKLeg>(*leg).unwrap().is_stepping() && arm.end_effector().distance(arm.target) < 0.001;
+            planted_for[i] = if planted { planted_for[i] + 1 } else { 0 };
+            if planted_for[i] > FRAMES_PER_TICK * 2 {
+                worst = worst.max(rendered_foot(&app, *leg).distance(feet[i]));
+                samples += 1;
+            }
+        }
+    }
+
+    assert!(samples > 100, "only {samples} frames with a foot down");
+    assert!(worst < 0.002, "planted feet slid by up to {worst}");
+}
+
+#[test]
+fn transforms_written_between_ticks_are_kept() {
+    let (mut app, creature) = app_at(2);
+    // Half way between two ticks.
+    run_ticks(&mut app, 1);
+    app.world_mut().get_mut::<Transform>(creature).unwrap().translation.x += 5.;
+    run_ticks(&mut app, 4);
+
+    assert!(body(&app, creature).x > 4., "teleport undone, back at {}", body(&app, creature));
+    assert!(simulated(&app, creature).translation.x > 4.);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    dismember::LegLost,
+    ground::Heightfield,
+    headless::{run_ticks, spawn_rig, spawn_rig_leg},
+    leg::{IKLeg, LegCreature, LegOf},
+};
+
+fn legs(app: &App, creature: Entity) -> Vec<Entity> {
+    app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect()
+}
+
+fn lost_legs(app: &App) -> Vec<(Entity, Entity)> {
+    let events = app.world().resource::<Events<LegLost>>();
+    events.get_reader().read(events).map(|lost| (lost.creature, lost.leg)).collect()
+}
+
+#[test]
+fn legs_point_back_at_their_creature() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    for leg_entity in legs(&app, creature) {
+        assert_eq!(app.world().get::<LegOf>(leg_entity).map(|leg_of| leg_of.creature), Some(creature));
+    }
+}
+
+#[test]
+fn despawned_legs_leave_the_creature() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let lost = legs(&app, creature)[0];
+
+    app.world_mut().despawn(lost);
+    assert_eq!(legs(&app, creature).len(), 3);
+    assert_eq!(lost_legs(&app), vec![(creature, lost)]);
+
+    // Three legs still hold it up, losing a second leaves too few.
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!(!app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+    let lost = legs(&app, creature)[0];
+    app.world_mut().despawn(lost);
+    run_ticks(&mut app, 1);
+    assert!(app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+}
+
+#[test]
+fn respawned_legs_join_the_creature() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let (lost, offset) = app.world().get::<LegCreature>(creature).unwrap().legs_info[0];
+    let side = app.world().get::<IKLeg>(lost).unwrap().leg_side;
+    let step_offset = app.world().get::<IKLeg>(lost).unwrap().step_offset;
+    app.world_mut().despawn(lost);
+    let second = legs(&app, creature)[0];
+    app.world_mut().despawn(second);
+    run_ticks(&mut app, 1);
+    assert!(app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+
+    let body = body(&app, creature);
+    let leg = spawn_rig_leg(app.world_mut(), body + offset, IKLeg::new(step_offset, 0.1, 0.15, 0.3, side, false));
+    app.world_mut().entity_mut(leg).insert(LegOf { creature, offset });
+    run_ticks(&mut app, 1);
+    assert!(legs(&app, creature).contains(&leg));
+    assert!(!app.world().get::<LegCreature>(creature).unwrap().is_collapsed());
+}
+
+#[test]
+fn reports_legs_that_were_never_there() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = spawn_rig(app.world_mut(), Vec3::Y * 0.2);
+    let missing = app.world_mut().spawn_empty().id();
+    app.world_mut().get_mut::<LegCreature>(creature).unwrap().legs_info.push((missing, Vec3::ZERO));
+
+    // The first update only starts the clock.
+    run_ticks(&mut app, 2);
+    assert_eq!(legs(&app, creature).len(), 4);
+    assert_eq!(lost_legs(&app), vec![(creature, missing)]);
+}
+
+#[test]
+fn legs_outlive_their_creature() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg_entities = legs(&app, creature);
+
+    app.world_mut().despawn(creature);
+    run_ticks(&mut app, 1);
+    for leg_entity in leg_entities {
+        assert!(app.world().get::<LegOf>(leg_entity).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    ground::Heightfield,
+    headless::run_ticks,
+    leg::LegCreature,
+    lod::{LocomotionLod, LodTier},
+};
+
+fn spawn_camera(app: &mut App, position: Vec3) -> Entity {
+    app.world_mut().spawn((Camera3d::default(), TransformBundle::from_transform(Transform::from_translation(position)))).id()
+}
+
+// A settled rig with LocomotionLod on its body and legs.
+fn lod_rig(app: &mut App, position: Vec3) -> Entity {
+    let creature = settled_rig(app, position);
+    let legs: Vec<Entity> = app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect();
+    for entity in legs.into_iter().chain([creature]) {
+        app.world_mut().entity_mut(entity).insert(LocomotionLod::default());
+    }
+    creature
+}
+
+fn tier(app: &App, entity: Entity) -> LodTier {
+    app.world().get::<LocomotionLod>(entity).unwrap().tier
+}
+
+#[test]
+fn tiers_follow_the_camera_distance_with_hysteresis() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let camera = spawn_camera(&mut app, Vec3::new(0., 0.2, -30.));
+    let creature = lod_rig(&mut app, Vec3::ZERO);
+    let move_camera = |app: &mut App, z: f32| {
+        app.world_mut().get_mut::<Transform>(camera).unwrap().translation.z = z;
+        run_ticks(app, 2);
+    };
+
+    move_camera(&mut app, -30.);
+    assert_eq!(tier(&app, creature), LodTier::Minimal);
+    // Legs copy the tier of their creature.
+    let leg = app.world().get::<LegCreature>(creature).unwrap().legs_info[0].0;
+    assert_eq!(tier(&app, leg), LodTier::Minimal);
+
+    // Just inside the Minimal threshold isn't enough to come back up, past the hysteresis is.
+    move_camera(&mut app, -24.5);
+    assert_eq!(tier(&app, creature), LodTier::Minimal);
+    move_camera(&mut app, -23.5);
+    assert_eq!(tier(&app, creature), LodTier::Reduced);
+    move_camera(&mut app, -5.);
+    assert_eq!(tier(&app, creature), LodTier::Full);
+    move_camera(&mut app, -60.);
+    assert_eq!(tier(&app, creature), LodTier::Baked);
+}
+
+#[test]
+fn far_creatures_update_less_often_and_keep_walking() {
+    let mut app = app_on(Heightfield::flat(0.));
+    spawn_camera(&mut app, Vec3::new(0., 0.2, -60.));
+    let creature = lod_rig(&mut app, Vec3::ZERO);
+    run_ticks(&mut app, 2);
+    assert_eq!(tier(&app, creature), LodTier::Baked);
+
+    let start = body(&app, creature);
+    let mut updates = 0;
+    walk(&mut app, creature, Vec3::Z * 0.4, 160, |app, _| {
+        updates += app.world().get::<LocomotionLod>(creature).unwrap().should_update() as u32;
+    });
+
+    assert_eq!(updates, 160 / LodTier::Baked.update_interval());
+    // Fully on the baked cycle by now.
+    assert_eq!(app.world().get::<LocomotionLod>(creature).unwrap().baked_blend(), 1.);
+    assert!(body(&app, creature).z - start.z > 1., "only got from {start} to {}", body(&app, creature));
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.1, "body at {}", body_height(&app, creature));
+}
+
+#[test]
+fn legs_follow_the_tier_of_their_creature_without_their_own_lod() {
+    let mut app = app_on(Heightfield::flat(0.));
+    spawn_camera(&mut app, Vec3::new(0., 0.2, -30.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    app.world_mut().entity_mut(creature).insert(LocomotionLod::default());
+    run_ticks(&mut app, 3);
+
+    assert_eq!(tier(&app, creature), LodTier::Minimal);
+    for (leg, _) in &app.world().get::<LegCreature>(creature).unwrap().legs_info {
+        assert_eq!(tier(&app, *leg), LodTier::Minimal);
+    }
+}
+
+#[test]
+fn far_creatures_keep_to_the_ground_up_a_ramp() {
+    // Flat where the creature goes baked, so it can't just carry on along the slope it was standing on.
+    const GRADE: f32 = 0.3;
+    let mut app = app_on(Heightfield::new(|point| (point.y - 1.).max(0.) * GRADE));
+    spawn_camera(&mut app, Vec3::new(0., 0.2, -60.));
+    let creature = lod_rig(&mut app, Vec3::ZERO);
+    let start = body(&app, creature);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        let height = body_height(app, creature);
+        assert!((0.05..0.4).contains(&height), "tick {tick}: body at {height}");
+    });
+    assert_eq!(app.world().get::<LocomotionLod>(creature).unwrap().baked_blend(), 1.);
+    assert!(body(&app, creature).z - start.z > 3., "only got from {start} to {}", body(&app, creature));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{
+    ground::Heightfield,
+    headless::run_ticks,
+    perception::{Perceivable, Perception, TargetLost, TargetSeen},
+};
+
+// Looking down +Z from the origin, 10 units ahead and 90 degrees wide, hearing 1 unit around.
+fn spawn_observer(app: &mut App) -> Entity {
+    app.world_mut().spawn((SpatialBundle::from_transform(Transform::from_xyz(0., 0.5, 0.)), Perception::new(10., 90_f32.to_radians(), Vec3::ZERO, 1.))).id()
+}
+
+fn spawn_target(app: &mut App, position: Vec3) -> Entity {
+    app.world_mut().spawn((SpatialBundle::from_transform(Transform::from_translation(position)), Perceivable::default())).id()
+}
+
+fn perception(app: &App, observer: Entity) -> &Perception {
+    app.world().get::<Perception>(observer).unwrap()
+}
+
+fn seen(app: &App) -> Vec<(Entity, Entity)> {
+    let events = app.world().resource::<Events<TargetSeen>>();
+    events.get_reader().read(events).map(|seen| (seen.observer, seen.target)).collect()
+}
+
+#[test]
+fn sees_targets_in_its_view_cone_only() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let observer = spawn_observer(&mut app);
+    let ahead = spawn_target(&mut app, Vec3::new(1., 0.5, 5.));
+    let behind = spawn_target(&mut app, Vec3::new(0., 0.5, -5.));
+    let too_far = spawn_target(&mut app, Vec3::new(0., 0.5, 20.));
+
+    run_ticks(&mut app, 2);
+    assert!(perception(&app, observer).sees(ahead));
+    assert!(!perception(&app, observer).sees(behind));
+    assert!(!perception(&app, observer).sees(too_far));
+    assert_eq!(seen(&app), vec![(observer, ahead)]);
+    assert_eq!(perception(&app, observer).last_known_position(ahead), Some(Vec3::new(1., 0.5, 5.)));
+}
+
+#[test]
+fn terrain_blocks_line_of_sight() {
+    // A ridge across the view, taller than the eye.
+    let mut app = app_on(Heightfield::new(|point| if (2. ..3.).contains(&point.y) { 2. } else { 0. }));
+    let observer = spawn_observer(&mut app);
+    let target = spawn_target(&mut app, Vec3::new(0., 0.5, 5.));
+
+    run_ticks(&mut app, 2);
+    assert!(!perception(&app, observer).sees(target));
+    assert!(seen(&app).is_empty());
+}
+
+#[test]
+fn hears_targets_it_cant_see() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let observer = spawn_observer(&mut app);
+    let quiet = spawn_target(&mut app, Vec3::new(0., 0.5, -0.8));
+    let loud = app.world_mut().spawn((SpatialBundle::from_transform(Transform::from_xyz(0., 0.5, -3.)), Perceivable { loudness: 4. })).id();
+    let silent = app.world_mut().spawn((SpatialBundle::from_transform(Transform::from_xyz(0., 0.5, -0.5)), Perceivable { loudness: 0. })).id();
+
+    run_ticks(&mut app, 2);
+    assert!(perception(&app, observer).hears(quiet));
+    assert!(perception(&app, observer).hears(loud));
+    assert!(!perception(&app, observer).hears(silent));
+    assert!(!perception(&app, observer).sees(quiet));
+}
+
+#[test]
+fn despawned_targets_are_lost_where_they_were_last_seen_and_forgotten() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let observer = spawn_observer(&mut app);
+    let target = spawn_target(&mut app, Vec3::new(0., 0.5, 5.));
+    run_ticks(&mut app, 2);
+    assert!(perception(&app, observer).sees(target));
+
+    app.world_mut().despawn(target);
+    // Long enough for the next perception update.
+    let lost = run_collecting(&mut app, 30, |lost: &TargetLost| (lost.observer, lost.target, lost.last_known_position));
+    assert!(!perception(&app, observer).sees(target));
+    assert_eq!(lost, vec![(observer, target, Vec3::new(0., 0.5, 5.))]);
+    assert_eq!(perception(&app, observer).last_known_position(target), None);
+}
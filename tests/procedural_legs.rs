@@ -0,0 +1,165 @@
+mod common;
+
+use bevy::{prelude::*, render::mesh::{Indices, VertexAttributeValues}};
+use common::*;
+use zombies::{
+    ground::Heightfield,
+    headless::{run_ticks, spawn_shaped_rig},
+    procedural::{leg_inverse_bindposes, leg_mesh, LegShape},
+    interpolation::SimTransform,
+    leg::LegCreature,
+    IKArm::{ArmRig, IKArm, RigReady},
+};
+
+fn long_legs() -> LegShape {
+    LegShape { lengths: vec![0.3, 0.45], ..default() }
+}
+
+fn three_bones() -> LegShape {
+    LegShape { lengths: vec![0.25, 0.25, 0.2], ..default() }
+}
+
+fn float3(mesh: &Mesh, attribute: impl Into<bevy::render::mesh::MeshVertexAttributeId>) -> Vec<Vec3> {
+    let Some(VertexAttributeValues::Float32x3(values)) = mesh.attribute(attribute) else {panic!("missing attribute")};
+    values.iter().map(|value| Vec3::from(*value)).collect()
+}
+
+#[test]
+fn skins_the_mesh_to_the_hip_and_the_knee() {
+    let shape = long_legs();
+    let mesh = leg_mesh(&shape);
+    let positions = float3(&mesh, Mesh::ATTRIBUTE_POSITION);
+    let Some(VertexAttributeValues::Float32x4(weights)) = mesh.attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT) else {panic!("missing weights")};
+    let Some(VertexAttributeValues::Uint16x4(joints)) = mesh.attribute(Mesh::ATTRIBUTE_JOINT_INDEX) else {panic!("missing joints")};
+    assert_eq!(weights.len(), positions.len());
+    assert_eq!(joints.len(), positions.len());
+
+    for ((position, weight), joint) in positions.iter().zip(weights).zip(joints) {
+        assert!((weight.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        let knee: f32 = (0..4).filter(|i| joint[*i] == 1).map(|i| weight[i]).sum();
+        if position.y < shape.lengths[0] - shape.joint_radius - 1e-4 {
+            assert_eq!(knee, 0., "vertex {position} below the knee");
+        } else if position.y > shape.lengths[0] + shape.joint_radius + 1e-4 {
+            assert_eq!(knee, 1., "vertex {position} above the knee");
+        }
+    }
+}
+
+#[test]
+fn skins_each_bone_to_the_joint_at_its_root() {
+    let shape = three_bones();
+    let mesh = leg_mesh(&shape);
+    let positions = float3(&mesh, Mesh::ATTRIBUTE_POSITION);
+    let Some(VertexAttributeValues::Float32x4(weights)) = mesh.attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT) else {panic!("missing weights")};
+    let Some(VertexAttributeValues::Uint16x4(joints)) = mesh.attribute(Mesh::ATTRIBUTE_JOINT_INDEX) else {panic!("missing joints")};
+    let inner_joints = &shape.joint_heights()[1..shape.lengths.len()];
+
+    for ((position, weight), joint) in positions.iter().zip(weights).zip(joints) {
+        assert!((weight.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        if inner_joints.iter().any(|height| (position.y - height).abs() < shape.joint_radius + 1e-4) {
+            continue;
+        }
+        let bone = inner_joints.iter().filter(|height| position.y > **height).count() as u16;
+        let followed: Vec<u16> = (0..4).filter(|i| weight[*i] > 0.).map(|i| joint[i]).collect();
+        assert_eq!(followed, vec![bone], "vertex {position} follows {joint:?} by {weight:?}");
+    }
+}
+
+#[test]
+fn faces_point_out() {
+    let mesh = leg_mesh(&LegShape::default());
+    let positions = float3(&mesh, Mesh::ATTRIBUTE_POSITION);
+    let normals = float3(&mesh, Mesh::ATTRIBUTE_NORMAL);
+    let Some(Indices::U32(indices)) = mesh.indices() else {panic!("missing indices")};
+    assert!(!indices.is_empty() && indices.len() % 3 == 0);
+
+    for triangle in indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let face = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        // Slivers at the poles of the joint balls have no area to orient.
+        if face.length() < 1e-9 {
+            continue;
+        }
+        assert!(face.dot(normals[a] + normals[b] + normals[c]) > 0., "triangle {triangle:?} faces in");
+    }
+}
+
+#[test]
+fn bind_poses_take_each_joint_to_the_origin() {
+    let shape = long_legs();
+    let inverse_bindposes = leg_inverse_bindposes(&shape);
+    let joints = [0., shape.lengths[0], shape.reach()];
+    assert_eq!(inverse_bindposes.len(), joints.len());
+    for (inverse_bindpose, height) in inverse_bindposes.iter().zip(joints) {
+        assert!(inverse_bindpose.transform_point3(Vec3::Y * height).length() < 1e-6);
+    }
+}
+
+#[test]
+fn bind_poses_take_every_joint_of_a_longer_chain_to_the_origin() {
+    let shape = three_bones();
+    let inverse_bindposes = leg_inverse_bindposes(&shape);
+    assert_eq!(inverse_bindposes.len(), 4);
+    for (inverse_bindpose, height) in inverse_bindposes.iter().zip(shape.joint_heights()) {
+        assert!(inverse_bindpose.transform_point3(Vec3::Y * height).length() < 1e-6);
+    }
+}
+
+#[test]
+#[should_panic(expected = "at least one bone")]
+fn refuses_a_leg_without_bones() {
+    leg_mesh(&LegShape { lengths: Vec::new(), ..default() });
+}
+
+#[test]
+#[should_panic(expected = "longer than zero")]
+fn refuses_bones_without_length() {
+    leg_mesh(&LegShape { lengths: vec![0.3, 0.], ..default() });
+}
+
+#[test]
+fn stands_on_legs_with_bones_of_different_lengths() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = spawn_shaped_rig(app.world_mut(), Vec3::Y * 0.2, &long_legs());
+    run_ticks(&mut app, 2);
+    let events = app.world().resource::<Events<RigReady>>();
+    assert!(events.get_reader().read(events).all(|ready| (ready.reach - 0.75).abs() < 1e-5));
+
+    run_ticks(&mut app, SETTLE_TICKS);
+    for foot in feet(&app, creature) {
+        assert!(foot.position.y.abs() < 0.001, "foot at {}", foot.position);
+        assert!(foot.end_effector.distance(foot.position) < 0.01, "end effector {} for foot {}", foot.end_effector, foot.position);
+    }
+}
+
+#[test]
+fn walks_on_legs_with_three_bones() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = spawn_shaped_rig(app.world_mut(), Vec3::Y * 0.2, &three_bones());
+    run_ticks(&mut app, 2);
+    let events = app.world().resource::<Events<RigReady>>();
+    assert!(events.get_reader().read(events).all(|ready| (ready.reach - 0.7).abs() < 1e-5));
+    run_ticks(&mut app, SETTLE_TICKS);
+
+    let legs: Vec<Entity> = app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg, _)| *leg).collect();
+    let solved = |app: &App| -> Vec<Vec3> { legs.iter().map(|leg| app.world().get::<IKArm>(*leg).unwrap().end_effector()).collect() };
+    let mut last_solved = solved(&app);
+    walk(&mut app, creature, Vec3::Z * 0.4, 120, |app, tick| {
+        assert!(worst_planted_foot_error(app, creature) < 0.001, "tick {tick}: planted foot off the ground");
+        for (leg, last_solved) in legs.iter().zip(&last_solved) {
+            let rig = app.world().get::<ArmRig>(*leg).unwrap();
+            assert_eq!(rig.joints.len(), 3);
+            // The skeleton is posed the way the chain was solved, rendered a tick behind like everything else.
+            let tip = app.world().get::<GlobalTransform>(rig.tip.unwrap()).unwrap().translation();
+            assert!(tip.distance(*last_solved) < 0.001, "tick {tick}: tip at {tip}, solved to {last_solved}");
+            let arm = app.world().get::<IKArm>(*leg).unwrap();
+            // Feet the body has walked away from are out of reach until they step.
+            let hip = app.world().get::<SimTransform>(*leg).unwrap().current().translation;
+            if hip.distance(arm.target) < three_bones().reach() - 0.01 {
+                assert!(arm.end_effector().distance(arm.target) < 0.001, "tick {tick}: end effector {} for foot {}", arm.end_effector(), arm.target);
+            }
+        }
+        last_solved = solved(app);
+    });
+    assert!(body(&app, creature).z > 0.5, "only got to {}", body(&app, creature));
+}
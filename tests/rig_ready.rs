@@ -0,0 +1,101 @@
+mod common;
+
+use bevy::{gizmos::gizmos::GizmoStorage, prelude::*, render::mesh::skinning::SkinnedMesh};
+use common::*;
+use zombies::{
+    ground::Heightfield,
+    headless::{run_ticks, spawn_rig, spawn_rig_leg},
+    leg::{IKLeg, LegSide},
+    IKArm::{ArmRig, IKArm, IKArmPlugin, RigReady},
+};
+
+fn ready_arms(app: &App) -> Vec<Entity> {
+    let events = app.world().resource::<Events<RigReady>>();
+    events.get_reader().read(events).map(|ready| ready.arm).collect()
+}
+
+fn loose_leg(app: &mut App, position: Vec3) -> (Entity, Entity) {
+    let leg = spawn_rig_leg(app.world_mut(), position, IKLeg::new(Vec3::new(0.3, -0.1, 0.), 0.1, 0.15, 0.3, LegSide::Left, false));
+    let skin = app.world().get::<Children>(leg).unwrap().iter()
+        .find(|child| app.world().get::<SkinnedMesh>(**child).is_some())
+        .copied()
+        .unwrap();
+    (leg, skin)
+}
+
+#[test]
+fn feet_start_on_the_ground() {
+    let mut app = app_on(Heightfield::new(|point| point.y * 0.3 + point.x * 0.1));
+    let creature = spawn_rig(app.world_mut(), Vec3::Y * 0.2);
+
+    // The first update only starts the clock.
+    run_ticks(&mut app, 2);
+    assert_eq!(ready_arms(&app).len(), 4);
+    assert!(worst_planted_foot_error(&app, creature) < 0.001, "feet {:?}", feet(&app, creature).iter().map(|foot| foot.position).collect::<Vec<_>>());
+}
+
+#[test]
+fn waits_for_the_skinned_mesh() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let (leg, skin) = loose_leg(&mut app, Vec3::Y * 0.3);
+    // Like a glTF leg whose scene hasn't been instanced yet.
+    let skinned_mesh = app.world_mut().entity_mut(skin).take::<SkinnedMesh>().unwrap();
+
+    run_ticks(&mut app, 10);
+    assert!(ready_arms(&app).is_empty());
+    assert!(app.world().get::<ArmRig>(leg).is_none());
+    assert_eq!(app.world().get::<IKArm>(leg).unwrap().target, Vec3::Y * 0.3);
+
+    app.world_mut().entity_mut(skin).insert(skinned_mesh);
+    run_ticks(&mut app, 1);
+    assert_eq!(ready_arms(&app), vec![leg]);
+    assert_eq!(app.world().get::<IKArm>(leg).unwrap().target, Vec3::new(0.3, 0., 0.));
+}
+
+#[test]
+fn never_readies_a_rig_without_enough_joints() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let (leg, skin) = loose_leg(&mut app, Vec3::Y * 0.3);
+    app.world_mut().get_mut::<SkinnedMesh>(skin).unwrap().joints.truncate(1);
+
+    run_ticks(&mut app, 10);
+    assert!(ready_arms(&app).is_empty());
+    assert!(app.world().get::<ArmRig>(leg).is_none());
+}
+
+#[test]
+fn finds_its_joints_again_after_losing_them() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let (leg, skin) = loose_leg(&mut app, Vec3::Y * 0.3);
+    run_ticks(&mut app, 2);
+    let joints = app.world().get::<ArmRig>(leg).unwrap().joints.clone();
+
+    app.world_mut().entity_mut(joints[1]).despawn_recursive();
+    run_ticks(&mut app, 1);
+    assert!(app.world().get::<ArmRig>(leg).is_none());
+
+    // The scene comes back with new joints.
+    let knee = app.world_mut().spawn(SpatialBundle::from_transform(Transform::from_xyz(0., 0.35, 0.))).id();
+    app.world_mut().entity_mut(joints[0]).add_child(knee);
+    app.world_mut().get_mut::<SkinnedMesh>(skin).unwrap().joints[1] = knee;
+    run_ticks(&mut app, 1);
+    assert_eq!(app.world().get::<ArmRig>(leg).unwrap().joints, [joints[0], knee]);
+}
+
+#[test]
+fn arms_solve_in_an_app_without_input() {
+    // No InputPlugin, just what the arm systems need.
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin, HierarchyPlugin, IKArmPlugin::new().in_schedule(Update)))
+        .init_resource::<GizmoConfigStore>()
+        .init_resource::<GizmoStorage<DefaultGizmoConfigGroup, ()>>();
+    app.world_mut().resource_mut::<GizmoConfigStore>().insert(GizmoConfig::default(), DefaultGizmoConfigGroup);
+    let (leg, _) = loose_leg(&mut app, Vec3::Y);
+    let target = Vec3::new(0.3, 0.6, 0.1);
+    app.world_mut().get_mut::<IKArm>(leg).unwrap().target = target;
+    run_ticks(&mut app, 3);
+
+    assert!(app.world().get::<ArmRig>(leg).is_some());
+    let end_effector = app.world().get::<IKArm>(leg).unwrap().end_effector();
+    assert!(end_effector.distance(target) < 0.001, "reached {end_effector}");
+}
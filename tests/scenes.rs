@@ -0,0 +1,307 @@
+mod common;
+
+use bevy::{
+    ecs::entity::EntityHashMap,
+    prelude::*,
+    render::mesh::skinning::SkinnedMesh,
+    scene::{ron, serde::SceneDeserializer, DynamicSceneBuilder},
+};
+use common::*;
+use serde::de::DeserializeSeed;
+use zombies::{
+    attack::Attack,
+    dismember::SeveredLeg,
+    grab::{Carried, Grab, Thrown},
+    ground::Heightfield,
+    headless::{run_ticks, spawn_rig, RIG_BONE_LENGTH},
+    health::{Damage, Dead, DeathPose, DeathResponse, Health},
+    interpolation::SimTransform,
+    jump::{Airborne, AirborneLeg, Jump},
+    leg::{IKLeg, LegCreature, LegOf},
+    lod::LocomotionLod,
+    perception::{Perceivable, Perception},
+    procedural::{LegAssets, LegShape, LegSkin},
+    ragdoll::{Crumple, CurledLeg},
+    IKArm::{ArmRig, IKArm, IKArmTarget},
+};
+
+// How long a restored creature is checked against the original for.
+const COMPARE_TICKS: u32 = 120;
+
+// The creature, then each leg in legs_info order followed by its joints, so two rigs line up entity for entity.
+fn rig_entities(world: &World, creature: Entity) -> Vec<Entity> {
+    let mut entities = vec![creature];
+    for (leg_entity, _) in &world.get::<LegCreature>(creature).unwrap().legs_info {
+        entities.extend(with_descendants(world, *leg_entity));
+    }
+    entities
+}
+
+// `root` followed by everything under it, depth first.
+fn with_descendants(world: &World, root: Entity) -> Vec<Entity> {
+    let mut entities = vec![root];
+    let mut stack = vec![root];
+    while let Some(entity) = stack.pop() {
+        let Some(children) = world.get::<Children>(entity) else {continue;};
+        entities.extend(children.iter());
+        stack.extend(children.iter().rev());
+    }
+    entities
+}
+
+fn save(app: &App, entities: &[Entity]) -> String {
+    let world = app.world();
+    let scene = DynamicSceneBuilder::from_world(world)
+        .deny_all()
+        .allow::<Transform>()
+        .allow::<GlobalTransform>()
+        .allow::<Parent>()
+        .allow::<Children>()
+        .allow::<SimTransform>()
+        .allow::<IKArm>()
+        .allow::<IKArmTarget>()
+        .allow::<IKLeg>()
+        .allow::<LegCreature>()
+        .allow::<LegOf>()
+        .allow::<LegSkin>()
+        .allow::<Jump>()
+        .allow::<Airborne>()
+        .allow::<AirborneLeg>()
+        .allow::<Attack>()
+        .allow::<Grab>()
+        .allow::<Carried>()
+        .allow::<Thrown>()
+        .allow::<Health>()
+        .allow::<DeathResponse>()
+        .allow::<Dead>()
+        .allow::<Crumple>()
+        .allow::<CurledLeg>()
+        .allow::<LocomotionLod>()
+        .allow::<SeveredLeg>()
+        .allow::<Perception>()
+        .allow::<Perceivable>()
+        .extract_entities(entities.iter().copied())
+        .build();
+    scene.serialize(&world.resource::<AppTypeRegistry>().read()).unwrap()
+}
+
+fn load(app: &mut App, saved: &str) -> DynamicScene {
+    let registry = app.world().resource::<AppTypeRegistry>().read();
+    let mut deserializer = ron::de::Deserializer::from_str(saved).unwrap();
+    SceneDeserializer { type_registry: &registry }.deserialize(&mut deserializer).unwrap()
+}
+
+// The body, where the feet are headed and where the solver put them, and wherever `others` are.
+fn poses(app: &App, creature: Entity, others: &[Entity]) -> Vec<Vec3> {
+    let mut poses = vec![body(app, creature)];
+    poses.extend(feet(app, creature).iter().flat_map(|foot| [foot.position, foot.end_effector]));
+    poses.extend(others.iter().map(|other| app.world().get::<Transform>(*other).unwrap().translation));
+    poses
+}
+
+// Saves the creature and `others` out of `app` and loads them into an app with nothing in it, legs skinned again
+// the way a game would. Checks both carry on the same, and hands back the restored app and entities.
+fn assert_restores_into_an_empty_world(app: &mut App, creature: Entity, others: &[Entity]) -> (App, EntityHashMap<Entity>) {
+    assert_restores_into(app, app_on(Heightfield::flat(0.)), creature, others)
+}
+
+// assert_restores_into_an_empty_world, loading into `restored_app` for levels with more in them than the ground.
+fn assert_restores_into(app: &mut App, mut restored_app: App, creature: Entity, others: &[Entity]) -> (App, EntityHashMap<Entity>) {
+    let mut entities = rig_entities(app.world(), creature);
+    entities.extend(others);
+    let saved = save(app, &entities);
+
+    let mut expected = Vec::new();
+    for _ in 0..COMPARE_TICKS {
+        run_ticks(app, 1);
+        expected.push(poses(app, creature, others));
+    }
+
+    // The first update only starts the clock, get it out of the way so both apps step in lockstep.
+    run_ticks(&mut restored_app, 1);
+    let scene = load(&mut restored_app, &saved);
+    let mut entity_map = EntityHashMap::default();
+    scene.write_to_world(restored_app.world_mut(), &mut entity_map).unwrap();
+    let restored = entity_map[&creature];
+    let restored_others: Vec<Entity> = others.iter().map(|other| entity_map[other]).collect();
+
+    let shape = LegShape { lengths: vec![RIG_BONE_LENGTH; 2], ..default() };
+    let assets = LegAssets { shape, mesh: Handle::default(), inverse_bindposes: Handle::default(), material: Handle::default() };
+    let restored_legs: Vec<Entity> = restored_app.world().get::<LegCreature>(restored).unwrap().legs_info.iter().map(|(leg, _)| *leg).collect();
+    // Severed legs among `others` have a skin to put back too.
+    for leg in restored_legs.iter().chain(&restored_others) {
+        assets.restore_skin(restored_app.world_mut(), *leg);
+    }
+
+    for (tick, expected) in expected.iter().enumerate() {
+        run_ticks(&mut restored_app, 1);
+        assert_eq!(&poses(&restored_app, restored, &restored_others), expected, "tick {tick}");
+    }
+    (restored_app, entity_map)
+}
+
+#[test]
+fn restores_a_creature_mid_stride() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 40);
+    assert!(feet(&app, creature).iter().any(|foot| foot.stepping), "should be saved mid-step");
+    let saved_entities = rig_entities(app.world(), creature);
+    let saved = save(&app, &saved_entities);
+
+    let mut expected = Vec::new();
+    for _ in 0..COMPARE_TICKS {
+        run_ticks(&mut app, 1);
+        expected.push(poses(&app, creature, &[]));
+    }
+
+    // A different level where the saved entity ids belong to something else, the rig has to be remapped onto
+    // a freshly spawned one.
+    let mut restored_app = app_on(Heightfield::flat(0.));
+    run_ticks(&mut restored_app, 1);
+    for _ in 0..saved_entities.len() {
+        restored_app.world_mut().spawn_empty();
+    }
+    let restored = spawn_rig(restored_app.world_mut(), Vec3::new(3., 0.2, -2.));
+    let scene = load(&mut restored_app, &saved);
+    let mut entity_map: EntityHashMap<Entity> = saved_entities.into_iter().zip(rig_entities(restored_app.world(), restored)).collect();
+    scene.write_to_world(restored_app.world_mut(), &mut entity_map).unwrap();
+
+    let restored_legs: Vec<Entity> = restored_app.world().get::<LegCreature>(restored).unwrap().legs_info.iter().map(|(leg, _)| *leg).collect();
+    assert!(restored_legs.iter().all(|leg| restored_app.world().get::<IKLeg>(*leg).is_some()), "legs_info wasn't remapped");
+    for (tick, expected) in expected.iter().enumerate() {
+        run_ticks(&mut restored_app, 1);
+        assert_eq!(&poses(&restored_app, restored, &[]), expected, "tick {tick}");
+    }
+}
+
+#[test]
+fn restores_a_creature_into_an_empty_world() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 40);
+    let (restored_app, entity_map) = assert_restores_into_an_empty_world(&mut app, creature, &[]);
+
+    let world = restored_app.world();
+    for (leg, _) in &world.get::<LegCreature>(entity_map[&creature]).unwrap().legs_info {
+        let rig = world.get::<ArmRig>(*leg).unwrap();
+        let skin = world.get::<Children>(*leg).unwrap().iter().find_map(|child| world.get::<SkinnedMesh>(*child)).unwrap();
+        assert_eq!(skin.joints[..rig.joints.len()], rig.joints);
+        assert_eq!(world.get::<Parent>(rig.joints[0]).unwrap().get(), *leg);
+        assert_eq!(world.get::<Parent>(rig.joints[1]).unwrap().get(), rig.joints[0]);
+    }
+}
+
+#[test]
+fn restores_a_creature_mid_jump() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    app.world_mut().entity_mut(creature).insert(Jump::new(Vec3::new(0., 2., 0.5)));
+    run_ticks(&mut app, 20);
+    assert!(app.world().get::<Airborne>(creature).is_some(), "should be saved in the air");
+    assert_restores_into_an_empty_world(&mut app, creature, &[]);
+}
+
+#[test]
+fn restores_an_attack_mid_strike() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = app.world().get::<LegCreature>(creature).unwrap().front_leg().unwrap();
+    let foot = app.world().get::<IKArm>(leg).unwrap().target;
+    let target = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_translation(foot + Vec3::new(0., 0.1, 0.25)))).id();
+    app.world_mut().entity_mut(leg).insert((Attack::default(), IKArmTarget { target }));
+    run_ticks(&mut app, 10);
+    assert!(app.world().get::<Attack>(leg).is_some(), "should be saved mid-attack");
+    let (restored_app, entity_map) = assert_restores_into_an_empty_world(&mut app, creature, &[target]);
+
+    assert!(restored_app.world().get::<Attack>(entity_map[&leg]).is_none(), "never finished the attack");
+}
+
+#[test]
+fn restores_a_creature_carrying_an_object() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = app.world().get::<LegCreature>(creature).unwrap().front_leg().unwrap();
+    let foot = app.world().get::<IKArm>(leg).unwrap().target;
+    let object = app.world_mut().spawn(TransformBundle::from_transform(Transform::from_translation(foot + Vec3::new(0., 0.1, 0.2)))).id();
+    app.world_mut().entity_mut(leg).insert((Grab::default(), IKArmTarget { target: object }));
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 60);
+    assert_eq!(app.world().get::<Grab>(leg).unwrap().held(), Some(object), "should be saved holding it");
+    let (restored_app, entity_map) = assert_restores_into_an_empty_world(&mut app, creature, &[object]);
+
+    assert_eq!(restored_app.world().get::<Grab>(entity_map[&leg]).unwrap().held(), Some(entity_map[&object]));
+    assert_eq!(restored_app.world().get::<Carried>(entity_map[&object]).unwrap().carrier(), entity_map[&leg]);
+}
+
+#[test]
+fn restores_a_creature_crumpling_dead() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    app.world_mut().entity_mut(creature).insert((Health::new(10.), DeathResponse { pose: DeathPose::Crumple, ..default() }));
+    app.world_mut().send_event(Damage { entity: creature, amount: 10., position: Vec3::ZERO });
+    run_ticks(&mut app, 5);
+    assert!(!app.world().get::<Crumple>(creature).unwrap().is_grounded(), "should be saved falling");
+    let (restored_app, entity_map) = assert_restores_into_an_empty_world(&mut app, creature, &[]);
+
+    assert!(restored_app.world().get::<Dead>(entity_map[&creature]).is_some());
+    assert!(restored_app.world().get::<Crumple>(entity_map[&creature]).unwrap().is_grounded());
+}
+
+#[test]
+fn restores_a_far_creature_with_what_it_saw_and_a_severed_leg() {
+    let camera_position = Vec3::new(0., 0.2, -30.);
+    let spawn_camera = |app: &mut App| {
+        app.world_mut().spawn((Camera3d::default(), TransformBundle::from_transform(Transform::from_translation(camera_position))));
+    };
+    let mut app = app_on(Heightfield::flat(0.));
+    spawn_camera(&mut app);
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    // Set up the way spawn_spider does, legs get their LocomotionLod from the body.
+    app.world_mut().entity_mut(creature).insert((
+        Perception::new(8., 120_f32.to_radians(), Vec3::new(0., 0.1, 0.15), 3.),
+        LocomotionLod::default(),
+    ));
+    let seen_at = Vec3::new(0., 0.3, 2.);
+    let target = app.world_mut().spawn((TransformBundle::from_transform(Transform::from_translation(seen_at)), Perceivable { loudness: 0. })).id();
+    run_ticks(&mut app, 20);
+    assert!(app.world().get::<Perception>(creature).unwrap().sees(target));
+    // Out of sight behind it, only remembered where it was.
+    app.world_mut().get_mut::<Transform>(target).unwrap().translation = Vec3::new(0., 0.3, -5.);
+    run_ticks(&mut app, 20);
+    assert!(!app.world().get::<Perception>(creature).unwrap().sees(target));
+
+    let severed = app.world().get::<LegCreature>(creature).unwrap().legs_info[0].0;
+    app.world_mut().entity_mut(severed).insert(SeveredLeg::new(Vec3::new(1., 2., 0.)));
+    set_heading(&mut app, creature, Vec3::Z * 0.4);
+    run_ticks(&mut app, 5);
+    assert_ne!(app.world().get::<LocomotionLod>(creature).unwrap().baked_phase(), 0., "should be saved mid-cycle");
+
+    let mut others = vec![target];
+    others.extend(with_descendants(app.world(), severed));
+    let mut restored_app = app_on(Heightfield::flat(0.));
+    spawn_camera(&mut restored_app);
+    // Take the saved entity ids, what the creature remembers has to be remapped to find the target.
+    for _ in 0..32 {
+        restored_app.world_mut().spawn_empty();
+    }
+    let (mut restored_app, entity_map) = assert_restores_into(&mut app, restored_app, creature, &others);
+
+    let restored = entity_map[&creature];
+    let perception = restored_app.world().get::<Perception>(restored).unwrap();
+    assert_eq!(perception.last_known_position(entity_map[&target]), Some(seen_at));
+    assert!(restored_app.world().get::<SeveredLeg>(entity_map[&severed]).is_some());
+    assert!(restored_app.world().get::<LegOf>(entity_map[&severed]).is_none());
+    // Same tier, same point in the baked cycle, and updating on the same ticks.
+    for tick in 0..8 {
+        let lod = app.world().get::<LocomotionLod>(creature).unwrap();
+        let restored_lod = restored_app.world().get::<LocomotionLod>(restored).unwrap();
+        assert_eq!(restored_lod.tier, lod.tier, "tick {tick}");
+        assert_eq!(restored_lod.baked_phase(), lod.baked_phase(), "tick {tick}");
+        assert_eq!(restored_lod.should_update(), lod.should_update(), "tick {tick}");
+        run_ticks(&mut app, 1);
+        run_ticks(&mut restored_app, 1);
+    }
+}
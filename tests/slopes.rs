@@ -0,0 +1,54 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{ground::Heightfield, leg::LegCreature};
+
+const GRADE: f32 = 0.3;
+
+fn slope() -> Heightfield {
+    Heightfield::new(|point| point.y * GRADE)
+}
+
+#[test]
+fn stands_on_a_slope_tilted_with_it() {
+    let mut app = app_on(slope());
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    assert!(worst_planted_foot_error(&app, creature) < 0.001);
+    let up = app.world().get::<LegCreature>(creature).unwrap().up();
+    let normal = Vec3::new(0., 1., -GRADE).normalize();
+    assert!(up.angle_between(normal) < 0.05, "body up {up}, slope normal {normal}");
+}
+
+#[test]
+fn walks_up_a_slope() {
+    let mut app = app_on(slope());
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let start = body(&app, creature);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        let height = body_height(app, creature);
+        assert!((0.1..0.4).contains(&height), "tick {tick}: body at {height}");
+        assert!(worst_planted_foot_error(app, creature) < 0.001, "tick {tick}: planted foot off the ground");
+    });
+
+    let end = body(&app, creature);
+    assert!(end.z - start.z > 5., "only got to {end}");
+    assert!(end.y - start.y > 5. * GRADE, "only climbed to {end}");
+}
+
+#[test]
+fn walks_down_a_slope() {
+    let mut app = app_on(slope());
+    let creature = settled_rig(&mut app, Vec3::new(0., 0., 6.));
+    let start = body(&app, creature);
+
+    walk(&mut app, creature, -Vec3::Z * 0.4, 300, |app, tick| {
+        let height = body_height(app, creature);
+        assert!((0.1..0.4).contains(&height), "tick {tick}: body at {height}");
+    });
+
+    let end = body(&app, creature);
+    assert!(start.y - end.y > 5. * GRADE, "only went down to {end}");
+}
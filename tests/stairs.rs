@@ -0,0 +1,52 @@
+mod common;
+
+use bevy::prelude::*;
+use common::*;
+use zombies::{ground::Heightfield, headless::run_ticks};
+
+const TREAD: f32 = 0.3;
+const RISE: f32 = 0.08;
+const STEPS: f32 = 15.;
+const TOP: f32 = TREAD * STEPS;
+
+// Flat until z = 0, then STEPS steps up to a landing.
+fn stairs() -> Heightfield {
+    Heightfield::new(|point| (point.y / TREAD).floor().clamp(0., STEPS) * RISE)
+}
+
+#[test]
+fn climbs_stairs_with_feet_on_the_treads() {
+    let mut app = app_on(stairs());
+    let creature = settled_rig(&mut app, Vec3::new(0., 0., -1.));
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 400, |app, tick| {
+        let height = body_height(app, creature);
+        assert!((0.1..0.45).contains(&height), "tick {tick}: body at {height}");
+        // Feet can land on the lip of a tread, but never halfway up a riser.
+        assert!(worst_planted_foot_error(app, creature) < RISE * 0.5, "tick {tick}: planted foot off a tread");
+    });
+
+    let end = body(&app, creature);
+    assert!(end.z > TOP + 0.5, "only got to {end}");
+    set_heading(&mut app, creature, Vec3::ZERO);
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.05, "body at {} on the landing", body_height(&app, creature));
+}
+
+#[test]
+fn walks_back_down_stairs() {
+    let mut app = app_on(stairs());
+    let creature = settled_rig(&mut app, Vec3::new(0., 0., TOP + 1.));
+
+    walk(&mut app, creature, -Vec3::Z * 0.4, 400, |app, tick| {
+        let height = body_height(app, creature);
+        assert!((0.1..0.45).contains(&height), "tick {tick}: body at {height}");
+        assert!(worst_planted_foot_error(app, creature) < RISE * 0.5, "tick {tick}: planted foot off a tread");
+    });
+
+    let end = body(&app, creature);
+    assert!(end.z < -0.5, "only got back to {end}");
+    set_heading(&mut app, creature, Vec3::ZERO);
+    run_ticks(&mut app, SETTLE_TICKS);
+    assert!((body_height(&app, creature) - 0.2).abs() < 0.05, "body at {} at the bottom", body_height(&app, creature));
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use bevy::{ecs::system::{RunSystemOnce, SystemParam}, gltf::{GltfExtras, GltfMaterialExtras, GltfMeshExtras}, prelude::*};
+use common::*;
+use zombies::{
+    foothold::{find_foothold, Foothold, FootholdRequest, FootholdSearch, SurfaceQuery, SurfaceRules, SurfaceTag},
+    ground::{GroundHit, GroundQuery, Heightfield},
+    headless::run_ticks,
+    leg::{IKLeg, LegCreature},
+};
+
+// Bands of the heightfield across Z, each its own entity so it can carry a SurfaceTag.
+#[derive(Resource, Default)]
+struct Strips(Vec<(f32, f32, Entity)>);
+
+#[derive(SystemParam)]
+struct StripGround<'w> {
+    heightfield: Res<'w, Heightfield>,
+    strips: Res<'w, Strips>,
+}
+
+impl GroundQuery for StripGround<'_> {
+    fn cast_ground_ray(&mut self, ray: Ray3d, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<GroundHit> {
+        let mut hit = self.heightfield.cast_ground_ray(ray, max_distance, filter)?;
+        hit.entity = self.strips.0.iter().find(|(start, end, _)| (*start..*end).contains(&hit.position.z)).map(|(.., strip)| *strip);
+        if hit.entity.is_some_and(|strip| !filter(strip)) {
+            return None;
+        }
+        Some(hit)
+    }
+}
+
+fn add_strips(world: &mut World, strips: &[(f32, f32, SurfaceTag)]) {
+    let strips = strips.iter().map(|(start, end, tag)| (*start, *end, world.spawn(*tag).id())).collect();
+    world.insert_resource(Strips(strips));
+}
+
+fn app_on_strips(strips: &[(f32, f32, SurfaceTag)]) -> App {
+    let mut app = app_with_ground::<StripGround<'static>>(Heightfield::flat(0.));
+    add_strips(app.world_mut(), strips);
+    app
+}
+
+fn leg_entities(app: &App, creature: Entity) -> Vec<Entity> {
+    app.world().get::<LegCreature>(creature).unwrap().legs_info.iter().map(|(leg_entity, _)| *leg_entity).collect()
+}
+
+// How far a settled rig gets in `ticks` ticks, and the longest any one step took.
+fn walk_on(strips: &[(f32, f32, SurfaceTag)], ticks: u32) -> (f32, u32) {
+    let mut app = app_on_strips(strips);
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    let leg = leg_entities(&app, creature)[0];
+    let (mut step_ticks, mut longest_step) = (0, 0);
+    walk(&mut app, creature, Vec3::Z * 0.4, ticks, |app, _| {
+        step_ticks = if app.world().get::<IKLeg>(leg).unwrap().is_stepping() { step_ticks + 1 } else { 0 };
+        longest_step = longest_step.max(step_ticks);
+    });
+    (body(&app, creature).z, longest_step)
+}
+
+fn search_strips(strips: &[(f32, f32, SurfaceTag)], rules: SurfaceRules, heightfield: Heightfield, desired_pos: Vec3) -> Option<Foothold> {
+    let mut world = World::new();
+    world.insert_resource(heightfield);
+    world.insert_resource(rules);
+    add_strips(&mut world, strips);
+    world.run_system_once(move |mut ground: StripGround, surfaces: SurfaceQuery| {
+        let search = FootholdSearch::default();
+        let transform = GlobalTransform::from_translation(desired_pos + Vec3::new(0., 0.2, -0.3));
+        let request = FootholdRequest { search: &search, transform: &transform, up: Vec3::Y, desired_pos, ray_budget: usize::MAX };
+        find_foothold(&mut ground, &surfaces, &request, None)
+    })
+}
+
+#[test]
+fn feet_never_plant_on_unwalkable_ground() {
+    let mut app = app_on_strips(&[(1., 3., SurfaceTag::Unwalkable)]);
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+
+    walk(&mut app, creature, Vec3::Z * 0.4, 300, |app, tick| {
+        for foot in feet(app, creature).iter().filter(|foot| !foot.stepping) {
+            assert!(!(1. ..3.).contains(&foot.position.z), "tick {tick}: foot planted on unwalkable ground at {}", foot.position);
+        }
+    });
+
+    assert!(body(&app, creature).z < 1., "walked on to {}", body(&app, creature));
+}
+
+#[test]
+fn feet_know_what_they_stand_on() {
+    let mut app = app_on_strips(&[(-10., 10., SurfaceTag::Sticky)]);
+    let creature = settled_rig(&mut app, Vec3::ZERO);
+    walk(&mut app, creature, Vec3::Z * 0.4, 60, |_, _| {});
+    run_ticks(&mut app, SETTLE_TICKS);
+
+    for leg in leg_entities(&app, creature) {
+        assert_eq!(app.world().get::<IKLeg>(leg).unwrap().surface(), SurfaceTag::Sticky);
+    }
+}
+
+#[test]
+fn slippery_ground_shortens_strides() {
+    let (walkable, _) = walk_on(&[], 200);
+    let (slippery, _) = walk_on(&[(-10., 10., SurfaceTag::Slippery)], 200);
+
+    assert!(slippery < walkable * 0.8, "{slippery} on slippery ground, {walkable} on walkable ground");
+}
+
+#[test]
+fn sticky_ground_slows_steps_down() {
+    let (walkable, walkable_step) = walk_on(&[], 200);
+    let (sticky, sticky_step) = walk_on(&[(-10., 10., SurfaceTag::Sticky)], 200);
+
+    assert!(sticky < walkable, "{sticky} on sticky ground, {walkable} on walkable ground");
+    // Steps take step_duration_scale times as long.
+    let scale = SurfaceRules::default().sticky.step_duration_scale;
+    assert!((sticky_step as f32 / walkable_step as f32 - scale).abs() < 0.2, "steps took {sticky_step} ticks on sticky ground, {walkable_step} on walkable ground");
+}
+
+#[test]
+fn costlier_surfaces_lose_to_nearby_cheaper_ones() {
+    // The desired position is slippery, with walkable ground a ring probe away.
+    let strips = [(-1., 0.05, SurfaceTag::Slippery)];
+    let cheap = search_strips(&strips, SurfaceRules::default(), Heightfield::flat(0.), Vec3::ZERO).unwrap();
+    let mut rules = SurfaceRules::default();
+    rules.slippery.cost = 3.;
+    let costly = search_strips(&strips, rules, Heightfield::flat(0.), Vec3::ZERO).unwrap();
+
+    assert_eq!(cheap.surface, SurfaceTag::Slippery);
+    assert_eq!(costly.surface, SurfaceTag::Walkable);
+    assert!(costly.position.z >= 0.05);
+}
+
+#[test]
+fn climbable_surfaces_ignore_the_slope_limit() {
+    // A 70 degree ramp.
+    let wall = || Heightfield::new(|point| point.y * 70_f32.to_radians().tan());
+    let walkable = search_strips(&[], SurfaceRules::default(), wall(), Vec3::ZERO);
+    let climbable = search_strips(&[(-10., 10., SurfaceTag::Climbable)], SurfaceRules::default(), wall(), Vec3::ZERO).unwrap();
+
+    assert!(walkable.is_none());
+    assert_eq!(climbable.surface, SurfaceTag::Climbable);
+    assert!(climbable.position.length() < 0.001, "planted at {}", climbable.position);
+}
+
+#[test]
+fn tags_come_from_gltf_extras() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let mut spawn_extras = |value: &str| app.world_mut().spawn(GltfExtras { value: value.to_string() }).id();
+    let ice = spawn_extras(r#"{"surface":"ice"}"#);
+    let named = spawn_extras(r#"{"name":"surface","surface":"Sticky"}"#);
+    let untagged = spawn_extras(r#"{"name":"surface"}"#);
+    let unknown = spawn_extras(r#"{"surface":"lava"}"#);
+    run_ticks(&mut app, 1);
+
+    let tag = |entity| app.world().get::<SurfaceTag>(entity).copied();
+    assert_eq!(tag(ice), Some(SurfaceTag::Slippery));
+    assert_eq!(tag(named), Some(SurfaceTag::Sticky));
+    assert_eq!(tag(untagged), None);
+    assert_eq!(tag(unknown), None);
+}
+
+#[test]
+fn tags_come_from_mesh_and_material_extras_too() {
+    let mut app = app_on(Heightfield::flat(0.));
+    let extras = |value: &str| value.to_string();
+    let mesh = app.world_mut().spawn(GltfMeshExtras { value: extras(r#"{"surface":"climbable"}"#) }).id();
+    let material = app.world_mut().spawn(GltfMaterialExtras { value: extras(r#"{"surface":"unwalkable"}"#) }).id();
+    let both = app.world_mut().spawn((
+        GltfMeshExtras { value: extras(r#"{"surface":"sticky"}"#) },
+        GltfMaterialExtras { value: extras(r#"{"surface":"ice"}"#) },
+    )).id();
+    let untagged_mesh = app.world_mut().spawn((
+        GltfMeshExtras { value: extras(r#"{"name":"rock"}"#) },
+        GltfMaterialExtras { value: extras(r#"{"surface":"ice"}"#) },
+    )).id();
+    run_ticks(&mut app, 1);
+
+    let tag = |entity| app.world().get::<SurfaceTag>(entity).copied();
+    assert_eq!(tag(mesh), Some(SurfaceTag::Climbable));
+    assert_eq!(tag(material), Some(SurfaceTag::Unwalkable));
+    assert_eq!(tag(both), Some(SurfaceTag::Sticky));
+    assert_eq!(tag(untagged_mesh), Some(SurfaceTag::Slippery));
+}